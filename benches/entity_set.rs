@@ -0,0 +1,67 @@
+//! Compares the `EntitySet` representations on the operations a query does most: building a set,
+//! probing it, and intersecting or uniting two of them.
+//!
+//! Run with `cargo bench --bench entity_set`.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rust_patterns::{
+    hashing::{FastHashSet, one_shot_64},
+    type_erasure::{
+        entity_set::{DenseBitSet, EntitySet, RoaringSet, SortedVecSet},
+        type_erased_api::EntityId,
+    },
+};
+
+/// About `count` pseudo-random IDs below `max`.
+fn ids(seed: u64, count: u64, max: u64) -> Vec<EntityId> {
+    (0..count).map(|i| one_shot_64(&(seed, i)) % max).collect()
+}
+
+/// Two sets of 10,000 entities each, with IDs drawn from ranges of the given sizes. The smaller the
+/// range, the denser the IDs.
+const DENSITIES: [(&str, u64); 3] = [
+    ("dense", 20_000),
+    ("medium", 1_000_000),
+    ("sparse", 1 << 40),
+];
+
+fn bench_representation<E: EntitySet + FromIterator<EntityId>>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(name);
+    for (density, max) in DENSITIES {
+        // A bitset over sparse IDs would need terabytes.
+        if name == "dense_bit_set" && max > 1_000_000 {
+            continue;
+        }
+        let (a, b) = (ids(1, 10_000, max), ids(2, 10_000, max));
+        let (set_a, set_b): (E, E) = (a.iter().copied().collect(), b.iter().copied().collect());
+
+        group.bench_with_input(BenchmarkId::new("build", density), &a, |bencher, a| {
+            bencher.iter(|| black_box(a.iter().copied().collect::<E>()))
+        });
+        group.bench_with_input(BenchmarkId::new("contains", density), &b, |bencher, b| {
+            bencher.iter(|| b.iter().filter(|id| set_a.contains(**id)).count())
+        });
+        group.bench_function(BenchmarkId::new("intersection", density), |bencher| {
+            bencher.iter(|| black_box(set_a.intersection(&set_b)))
+        });
+        group.bench_function(BenchmarkId::new("union", density), |bencher| {
+            bencher.iter(|| black_box(set_a.union(&set_b)))
+        });
+        group.bench_function(BenchmarkId::new("difference", density), |bencher| {
+            bencher.iter(|| black_box(set_a.difference(&set_b)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_entity_sets(c: &mut Criterion) {
+    bench_representation::<FastHashSet<EntityId>>(c, "hash_set");
+    bench_representation::<SortedVecSet>(c, "sorted_vec_set");
+    bench_representation::<DenseBitSet>(c, "dense_bit_set");
+    bench_representation::<RoaringSet>(c, "roaring_set");
+}
+
+criterion_group!(benches, bench_entity_sets);
+criterion_main!(benches);
@@ -0,0 +1,114 @@
+//! Compares `Index`, which caches the hash of each stored key, with the design it replaced, which
+//! recomputed the hash of every stored key it probed and every key it moved when the table grew.
+//!
+//! Run with `cargo bench --bench index`.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use hashbrown::HashTable;
+use rust_patterns::{
+    hashing::{FastHashSet, Fingerprint, one_shot_128},
+    type_erasure::type_erased_api::{EntityId, Index, TypeErasedIndex},
+};
+
+/// The previous design: only the key is stored, so the table's hasher and equality closures hash it
+/// again whenever they look at it.
+#[derive(Default)]
+struct RehashingIndex {
+    lookup: HashTable<(String, FastHashSet<EntityId>)>,
+}
+
+impl RehashingIndex {
+    fn insert_entity(&mut self, key: &String, entity_id: EntityId) -> bool {
+        let hash = one_shot_128(key);
+        let (_, set) = self
+            .lookup
+            .entry(
+                hash as u64,
+                |(stored, _)| one_shot_128(stored) == hash,
+                |(stored, _)| one_shot_128(stored) as u64,
+            )
+            .or_insert_with(|| (key.clone(), FastHashSet::default()))
+            .into_mut();
+        set.insert(entity_id)
+    }
+
+    fn get_with_hash(&self, hash: Fingerprint) -> Option<&FastHashSet<EntityId>> {
+        self.lookup
+            .find(hash.as_u64(), |(stored, _)| {
+                one_shot_128(stored) == hash.as_u128()
+            })
+            .map(|(_, set)| set)
+    }
+}
+
+/// `count` distinct keys of `len` bytes each.
+fn keys(count: usize, len: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            let prefix = format!("{i:08}");
+            prefix.repeat(len / prefix.len())
+        })
+        .collect()
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for len in [16, 1024, 16 * 1024] {
+        let keys = keys(1000, len);
+        group.bench_with_input(BenchmarkId::new("cached", len), &keys, |b, keys| {
+            b.iter(|| {
+                let mut index = Index::<String>::new();
+                for (i, key) in keys.iter().enumerate() {
+                    index.insert_entity(key, i as EntityId).unwrap();
+                }
+                black_box(index)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("rehashing", len), &keys, |b, keys| {
+            b.iter(|| {
+                let mut index = RehashingIndex::default();
+                for (i, key) in keys.iter().enumerate() {
+                    index.insert_entity(key, i as EntityId);
+                }
+                black_box(index)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_lookup_by_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup_by_hash");
+    for len in [16, 1024, 16 * 1024] {
+        let keys = keys(1000, len);
+        let hashes: Vec<Fingerprint> = keys.iter().map(Fingerprint::of).collect();
+
+        let mut cached = Index::<String>::new();
+        let mut rehashing = RehashingIndex::default();
+        for (i, key) in keys.iter().enumerate() {
+            cached.insert_entity(key, i as EntityId).unwrap();
+            rehashing.insert_entity(key, i as EntityId);
+        }
+
+        group.bench_with_input(BenchmarkId::new("cached", len), &hashes, |b, hashes| {
+            b.iter(|| {
+                for &hash in hashes {
+                    black_box(cached.get_with_hash(hash));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("rehashing", len), &hashes, |b, hashes| {
+            b.iter(|| {
+                for &hash in hashes {
+                    black_box(rehashing.get_with_hash(hash));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert, bench_lookup_by_hash);
+criterion_main!(benches);
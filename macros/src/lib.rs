@@ -0,0 +1,112 @@
+/*!
+Procedural macros for [`rust_patterns`](https://www.robertjacobson.dev/rust_patterns/rust_patterns/).
+
+These live in their own crate only because proc macros have to. The traits they implement, and the
+documentation of what the generated code does, live in the main crate.
+*/
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Fields, GenericParam, Generics, Index, parse_macro_input, parse_quote,
+};
+
+/// Derives `rust_patterns::hashing::StableHash`.
+///
+/// Structs hash their fields in declaration order. Enums first hash the zero-based declaration
+/// index of the variant as a `u32`, then the variant's fields in declaration order. Every type
+/// parameter gets a `StableHash` bound. Unions are not supported.
+#[proc_macro_derive(StableHash)]
+pub fn derive_stable_hash(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_stable_hash(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_stable_hash(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = add_trait_bounds(input.generics.clone());
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, bindings) = destructure(quote!(Self), &data.fields);
+            quote! {
+                let #pattern = self;
+                #( ::rust_patterns::hashing::StableHash::stable_hash(#bindings, state); )*
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().enumerate().map(|(tag, variant)| {
+                let tag = tag as u32;
+                let variant_name = &variant.ident;
+                let (pattern, bindings) = destructure(quote!(Self::#variant_name), &variant.fields);
+                quote! {
+                    #pattern => {
+                        ::rust_patterns::hashing::StableHash::stable_hash(&#tag, state);
+                        #( ::rust_patterns::hashing::StableHash::stable_hash(#bindings, state); )*
+                    }
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "`StableHash` cannot be derived for unions",
+            ));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::rust_patterns::hashing::StableHash for #name #type_generics #where_clause {
+            fn stable_hash<__H: ::core::hash::Hasher>(&self, state: &mut __H) {
+                #body
+            }
+        }
+    })
+}
+
+/// Builds a pattern that binds every field of `path` by reference, returning the pattern and the
+/// bindings in declaration order.
+fn destructure(path: TokenStream2, fields: &Fields) -> (TokenStream2, Vec<syn::Ident>) {
+    match fields {
+        Fields::Named(named) => {
+            let names: Vec<_> = named
+                .named
+                .iter()
+                .map(|field| field.ident.clone().unwrap())
+                .collect();
+            let bindings: Vec<_> = names
+                .iter()
+                .map(|name| format_ident!("__field_{}", name))
+                .collect();
+            (quote!(#path { #( #names: #bindings ),* }), bindings)
+        }
+        Fields::Unnamed(unnamed) => {
+            let indices = (0..unnamed.unnamed.len()).map(Index::from);
+            let bindings: Vec<_> = (0..unnamed.unnamed.len())
+                .map(|i| syn::Ident::new(&format!("__field_{i}"), Span::call_site()))
+                .collect();
+            (quote!(#path { #( #indices: #bindings ),* }), bindings)
+        }
+        Fields::Unit => (quote!(#path), Vec::new()),
+    }
+}
+
+fn add_trait_bounds(mut generics: Generics) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(type_param) = param {
+            type_param
+                .bounds
+                .push(parse_quote!(::rust_patterns::hashing::StableHash));
+        }
+    }
+    generics
+}
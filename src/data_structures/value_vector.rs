@@ -61,6 +61,10 @@ A simple way to ensure soundness of `ValueVec<T>` is to require `T` to be `Copy`
 We can prevent construction of `ValueVec`s for values with destructors by using the `needs_drop` function:
 
 ```rust
+# use std::mem::needs_drop;
+# struct ValueVec<V> {
+#     values: Vec<V>,
+# }
 impl<V> ValueVec<V> {
     pub fn new() -> Self {
         // Panics if `V` has a destructor.
@@ -71,6 +75,7 @@ impl<V> ValueVec<V> {
         }
     }
 }
+# let _ = ValueVec::<u32>::new();
 ```
 
 ## Homework assignments
@@ -83,7 +88,8 @@ impl<V> ValueVec<V> {
 
 */
 
-use std::{cell::UnsafeCell, fmt::Debug};
+use alloc::vec::Vec;
+use core::{cell::UnsafeCell, fmt::Debug};
 
 /// A by-value, `ref`-less vector with interior mutability. Values of type `V` can be moved into and out of the vector.
 /// Cloning / copying getters exist if `V` implements `Clone`/`Copy`. See the module-level docs for important notes
@@ -278,7 +284,7 @@ impl<V> Default for ValueVec<V> {
 }
 
 impl<V: Debug> Debug for ValueVec<V> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         // SAFETY: We create a temporary shared reference to the inner Vec.
         // No mutable borrows of the Vec exist concurrently by design.
         let vec = unsafe { &*self.data.get() };
@@ -303,7 +309,7 @@ impl<V> From<ValueVec<V>> for Vec<V> {
 
 impl<V> IntoIterator for ValueVec<V> {
     type Item = V;
-    type IntoIter = std::vec::IntoIter<V>;
+    type IntoIter = alloc::vec::IntoIter<V>;
 
     fn into_iter(self) -> Self::IntoIter {
         // SAFETY: We are consuming `self`, so there can be no remaining references
@@ -0,0 +1,158 @@
+/*!
+
+# Hash Maps and Hash Sets by Use Case
+
+This is the advice from the [module docs](super) taken literally. The rest of the crate never
+names `hashbrown`, `indexmap`, or `std::collections::HashMap` directly. It names the _use case_:
+
+| Alias                     | Use case                                               | Backend (cargo feature)                                            |
+|---------------------------|--------------------------------------------------------|--------------------------------------------------------------------|
+| [`FastHashMap`], [`FastHashSet`] | Internal tables keyed by trusted data           | `hashbrown` with xxh3 (default), `fast-hash-foldhash`, or `fast-hash-ahash` |
+| [`DeterministicHashMap`]  | Iteration order must be reproducible (output, tests)   | `indexmap` with a fixed-seed xxh3; iterates in insertion order     |
+| [`FingerprintMap`], [`FingerprintSet`] | Keys that are already [`Fingerprint`](super::Fingerprint)s | `hashbrown` with the identity [`FingerprintHasher`](super::FingerprintHasher) |
+| [`DosResistantHashMap`]   | Keys come from an adversary (network, user input)      | `std` with randomly keyed SipHash-1-3 (default), or `dos-resistant-ahash` |
+
+Swapping the backend for one use case is a one-line change to `Cargo.toml` (or to this file),
+and it happens everywhere at once. If both `fast-hash-foldhash` and `fast-hash-ahash` are enabled,
+foldhash wins, because cargo features have to be additive. [`DosResistantHashMap`] needs the `std`
+feature, because that's where its random keys come from.
+
+Each alias comes with constructor functions. The aliases all implement `Default`, but a free
+function is the only way to write "a new fast map" without also writing the hasher type.
+
+*/
+
+#[cfg(not(any(feature = "fast-hash-foldhash", feature = "fast-hash-ahash")))]
+use super::Xxh3BuildHasher;
+
+/// The `BuildHasher` backing [`FastHashMap`] and [`FastHashSet`].
+#[cfg(not(any(feature = "fast-hash-foldhash", feature = "fast-hash-ahash")))]
+pub type FastBuildHasher = Xxh3BuildHasher;
+/// The `BuildHasher` backing [`FastHashMap`] and [`FastHashSet`].
+#[cfg(feature = "fast-hash-foldhash")]
+pub type FastBuildHasher = foldhash::fast::RandomState;
+/// The `BuildHasher` backing [`FastHashMap`] and [`FastHashSet`].
+#[cfg(all(feature = "fast-hash-ahash", not(feature = "fast-hash-foldhash")))]
+pub type FastBuildHasher = ahash::RandomState;
+
+/// A hash map for trusted keys where raw speed is all that matters.
+pub type FastHashMap<K, V> = hashbrown::HashMap<K, V, FastBuildHasher>;
+/// A hash set for trusted keys where raw speed is all that matters.
+pub type FastHashSet<T> = hashbrown::HashSet<T, FastBuildHasher>;
+
+/// The `BuildHasher` backing [`DeterministicHashMap`]. It has to be the same in every process,
+/// so it can't be randomly seeded.
+pub type DeterministicBuildHasher = super::Xxh3BuildHasher;
+
+/// A hash map that iterates in insertion order, so that iteration is reproducible from run to
+/// run and from machine to machine.
+pub type DeterministicHashMap<K, V> = indexmap::IndexMap<K, V, DeterministicBuildHasher>;
+
+/// A hash map keyed by values that are already hashes, so the keys aren't hashed again.
+pub type FingerprintMap<V> =
+    hashbrown::HashMap<super::Fingerprint, V, super::BuildFingerprintHasher>;
+/// A hash set of values that are already hashes, so they aren't hashed again.
+pub type FingerprintSet = hashbrown::HashSet<super::Fingerprint, super::BuildFingerprintHasher>;
+
+/// The `BuildHasher` backing [`DosResistantHashMap`].
+#[cfg(all(feature = "std", not(feature = "dos-resistant-ahash")))]
+pub type DosResistantBuildHasher = std::hash::RandomState;
+/// The `BuildHasher` backing [`DosResistantHashMap`].
+#[cfg(feature = "dos-resistant-ahash")]
+pub type DosResistantBuildHasher = ahash::RandomState;
+
+/// A hash map whose hash function is randomly keyed per map, so an adversary who controls the
+/// keys can't engineer collisions.
+#[cfg(all(feature = "std", not(feature = "dos-resistant-ahash")))]
+pub type DosResistantHashMap<K, V> = std::collections::HashMap<K, V, DosResistantBuildHasher>;
+/// A hash map whose hash function is randomly keyed per map, so an adversary who controls the
+/// keys can't engineer collisions.
+#[cfg(feature = "dos-resistant-ahash")]
+pub type DosResistantHashMap<K, V> = hashbrown::HashMap<K, V, DosResistantBuildHasher>;
+
+pub fn fast_hash_map<K, V>() -> FastHashMap<K, V> {
+    FastHashMap::default()
+}
+
+pub fn fast_hash_map_with_capacity<K, V>(capacity: usize) -> FastHashMap<K, V> {
+    FastHashMap::with_capacity_and_hasher(capacity, FastBuildHasher::default())
+}
+
+pub fn fast_hash_set<T>() -> FastHashSet<T> {
+    FastHashSet::default()
+}
+
+pub fn fast_hash_set_with_capacity<T>(capacity: usize) -> FastHashSet<T> {
+    FastHashSet::with_capacity_and_hasher(capacity, FastBuildHasher::default())
+}
+
+pub fn deterministic_hash_map<K, V>() -> DeterministicHashMap<K, V> {
+    DeterministicHashMap::default()
+}
+
+pub fn deterministic_hash_map_with_capacity<K, V>(capacity: usize) -> DeterministicHashMap<K, V> {
+    DeterministicHashMap::with_capacity_and_hasher(capacity, DeterministicBuildHasher::default())
+}
+
+#[cfg(feature = "std")]
+pub fn dos_resistant_hash_map<K, V>() -> DosResistantHashMap<K, V> {
+    DosResistantHashMap::with_hasher(DosResistantBuildHasher::default())
+}
+
+#[cfg(feature = "std")]
+pub fn dos_resistant_hash_map_with_capacity<K, V>(capacity: usize) -> DosResistantHashMap<K, V> {
+    DosResistantHashMap::with_capacity_and_hasher(capacity, DosResistantBuildHasher::default())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use std::hash::BuildHasher;
+
+    use super::*;
+
+    #[test]
+    fn fast_collections() {
+        let mut map = fast_hash_map_with_capacity(4);
+        map.insert("a", 1);
+        map.insert("b", 2);
+        assert_eq!(map.get("a"), Some(&1));
+
+        let mut set: FastHashSet<u64> = fast_hash_set();
+        assert!(set.insert(1));
+        assert!(!set.insert(1));
+    }
+
+    #[test]
+    fn deterministic_map_iterates_in_insertion_order() {
+        let mut map = deterministic_hash_map();
+        for key in [9, 3, 7, 1, 5] {
+            map.insert(key, key * 10);
+        }
+        map.insert(3, 0);
+        let keys: Vec<_> = map.keys().copied().collect();
+        assert_eq!(keys, [9, 3, 7, 1, 5]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn dos_resistant_maps_are_keyed_per_map() {
+        let mut a = dos_resistant_hash_map_with_capacity(1);
+        a.insert("key", 1);
+        assert_eq!(a.get("key"), Some(&1));
+
+        // Separately constructed maps almost surely disagree about the hash of the same key.
+        let b: DosResistantHashMap<&str, i32> = dos_resistant_hash_map();
+        let hashes: FastHashSet<u64> = (0..4)
+            .map(|_| {
+                let c: DosResistantHashMap<&str, i32> = dos_resistant_hash_map();
+                BuildHasher::hash_one(c.hasher(), "key")
+            })
+            .chain([
+                BuildHasher::hash_one(a.hasher(), "key"),
+                BuildHasher::hash_one(b.hasher(), "key"),
+            ])
+            .collect();
+        assert!(hashes.len() > 1);
+    }
+}
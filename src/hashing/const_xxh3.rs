@@ -0,0 +1,463 @@
+/*!
+
+# Compile-Time XXH3
+
+`TypeId` and the address of a static are fine for telling types apart _within_ one build of one
+binary, but both change from build to build, so neither can be persisted, sent over the wire, or
+compared across processes. A hash of the type's fully qualified name can, and if the hash can be
+computed by a `const fn`, it costs nothing at runtime and lives in read-only memory.
+
+[`xxh3_128`] is a `const fn` port of the 128-bit XXH3 algorithm. Its output is identical to
+[`XxHash3_128::oneshot`](twox_hash::XxHash3_128::oneshot), and therefore to writing the same bytes
+to an [`Xxh3Hasher128`](super::Xxh3Hasher128), for every input length and seed. The
+[`const_fingerprint!`](crate::const_fingerprint) macro wraps it to turn a string literal into a
+[`Fingerprint`](super::Fingerprint) at compile time:
+
+```rust
+use rust_patterns::{const_fingerprint, hashing::Fingerprint};
+
+const ID: Fingerprint = const_fingerprint!(concat!(module_path!(), "::", "Widget"));
+```
+
+Note that these are digests of the _raw bytes_ of the string. They are not equal to
+`Fingerprint::of(&"...")`, because the `Hash` impl of `str` appends a terminator byte.
+
+The port favors clarity over speed: it's a straightforward scalar implementation with no SIMD. At
+runtime, prefer `twox_hash`.
+
+*/
+
+/// The default XXH3 secret from the reference implementation.
+#[rustfmt::skip]
+const DEFAULT_SECRET: [u8; SECRET_LEN] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+const SECRET_LEN: usize = 192;
+/// Inputs longer than this take the striped "large" path and use a secret derived from the seed.
+const MID_SIZE_MAX: usize = 240;
+const STRIPE_LEN: usize = 64;
+const STRIPES_PER_BLOCK: usize = (SECRET_LEN - STRIPE_LEN) / 8;
+const BLOCK_LEN: usize = STRIPE_LEN * STRIPES_PER_BLOCK;
+
+const PRIME32_1: u64 = 0x9E3779B1;
+const PRIME32_2: u64 = 0x85EBCA77;
+const PRIME32_3: u64 = 0xC2B2AE3D;
+const PRIME64_1: u64 = 0x9E3779B185EBCA87;
+const PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+const PRIME64_3: u64 = 0x165667B19E3779F9;
+const PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+const PRIME64_5: u64 = 0x27D4EB2F165667C5;
+const PRIME_MX1: u64 = 0x165667919E3779F9;
+const PRIME_MX2: u64 = 0x9FB21C651E98DF25;
+
+/// The 128-bit XXH3 digest of `input` with the default seed, computable at compile time.
+pub const fn xxh3_128(input: &[u8]) -> u128 {
+    xxh3_128_with_seed(super::DEFAULT_SEED, input)
+}
+
+/// The 128-bit XXH3 digest of `input` with `seed`, computable at compile time.
+pub const fn xxh3_128_with_seed(seed: u64, input: &[u8]) -> u128 {
+    let secret = &DEFAULT_SECRET;
+    match input.len() {
+        0 => hash_0(secret, seed),
+        1..=3 => hash_1_to_3(secret, seed, input),
+        4..=8 => hash_4_to_8(secret, seed, input),
+        9..=16 => hash_9_to_16(secret, seed, input),
+        17..=128 => hash_17_to_128(secret, seed, input),
+        129..=MID_SIZE_MAX => hash_129_to_240(secret, seed, input),
+        _ => hash_large(&derive_secret(seed), input),
+    }
+}
+
+const fn from_halves(low: u64, high: u64) -> u128 {
+    ((high as u128) << 64) | low as u128
+}
+
+const fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+const fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+        bytes[offset + 4],
+        bytes[offset + 5],
+        bytes[offset + 6],
+        bytes[offset + 7],
+    ])
+}
+
+/// Multiplies to 128 bits and folds the product back down to 64.
+const fn mul_fold(a: u64, b: u64) -> u64 {
+    let product = (a as u128).wrapping_mul(b as u128);
+    product as u64 ^ (product >> 64) as u64
+}
+
+const fn avalanche(mut x: u64) -> u64 {
+    x ^= x >> 37;
+    x = x.wrapping_mul(PRIME_MX1);
+    x ^ (x >> 32)
+}
+
+const fn avalanche_xxh64(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(PRIME64_2);
+    x ^= x >> 29;
+    x = x.wrapping_mul(PRIME64_3);
+    x ^ (x >> 32)
+}
+
+const fn hash_0(secret: &[u8], seed: u64) -> u128 {
+    let low = avalanche_xxh64(seed ^ read_u64(secret, 64) ^ read_u64(secret, 72));
+    let high = avalanche_xxh64(seed ^ read_u64(secret, 80) ^ read_u64(secret, 88));
+    from_halves(low, high)
+}
+
+const fn hash_1_to_3(secret: &[u8], seed: u64, input: &[u8]) -> u128 {
+    let len = input.len();
+    let combined = (input[len - 1] as u32)
+        | ((len as u32) << 8)
+        | ((input[0] as u32) << 16)
+        | ((input[len >> 1] as u32) << 24);
+
+    let low_secret = (read_u32(secret, 0) ^ read_u32(secret, 4)) as u64;
+    let high_secret = (read_u32(secret, 8) ^ read_u32(secret, 12)) as u64;
+    let low = low_secret.wrapping_add(seed) ^ combined as u64;
+    let high = high_secret.wrapping_sub(seed) ^ combined.swap_bytes().rotate_left(13) as u64;
+    from_halves(avalanche_xxh64(low), avalanche_xxh64(high))
+}
+
+const fn hash_4_to_8(secret: &[u8], seed: u64, input: &[u8]) -> u128 {
+    let len = input.len();
+    let first = read_u32(input, 0) as u64;
+    let last = read_u32(input, len - 4) as u64;
+    let modified_seed = seed ^ (((seed as u32).swap_bytes() as u64) << 32);
+
+    let combined = first | (last << 32);
+    let lhs = (read_u64(secret, 16) ^ read_u64(secret, 24)).wrapping_add(modified_seed) ^ combined;
+    let rhs = PRIME64_1.wrapping_add((len as u64) << 2);
+    let product = (lhs as u128).wrapping_mul(rhs as u128);
+
+    let mut low = product as u64;
+    let mut high = (product >> 64) as u64;
+    high = high.wrapping_add(low << 1);
+    low ^= high >> 3;
+    low ^= low >> 35;
+    low = low.wrapping_mul(PRIME_MX2);
+    low ^= low >> 28;
+    from_halves(low, avalanche(high))
+}
+
+const fn hash_9_to_16(secret: &[u8], seed: u64, input: &[u8]) -> u128 {
+    let len = input.len();
+    let first = read_u64(input, 0);
+    let last = read_u64(input, len - 8);
+
+    let val1 = (read_u64(secret, 32) ^ read_u64(secret, 40)).wrapping_sub(seed) ^ first ^ last;
+    let val2 = (read_u64(secret, 48) ^ read_u64(secret, 56)).wrapping_add(seed) ^ last;
+    let product = (val1 as u128).wrapping_mul(PRIME64_1 as u128);
+
+    let low = (product as u64).wrapping_add(((len - 1) as u64) << 54);
+    let high = ((product >> 64) as u64)
+        .wrapping_add(val2 & 0xFFFF_FFFF_0000_0000)
+        .wrapping_add((val2 & 0xFFFF_FFFF).wrapping_mul(PRIME32_2));
+    let low = low ^ high.swap_bytes();
+
+    let q = from_halves(low, high).wrapping_mul(PRIME64_2 as u128);
+    from_halves(avalanche(q as u64), avalanche((q >> 64) as u64))
+}
+
+/// Mixes 16 bytes of input at `data` with 16 bytes of secret at `key`.
+const fn mix_16(input: &[u8], data: usize, secret: &[u8], key: usize, seed: u64) -> u64 {
+    mul_fold(
+        read_u64(input, data) ^ read_u64(secret, key).wrapping_add(seed),
+        read_u64(input, data + 8) ^ read_u64(secret, key + 8).wrapping_sub(seed),
+    )
+}
+
+/// Mixes two 16-byte chunks of input at `data1` and `data2` into both accumulators.
+const fn mix_32(
+    acc: &mut [u64; 2],
+    input: &[u8],
+    data1: usize,
+    data2: usize,
+    secret: &[u8],
+    key: usize,
+    seed: u64,
+) {
+    acc[0] = acc[0].wrapping_add(mix_16(input, data1, secret, key, seed));
+    acc[1] = acc[1].wrapping_add(mix_16(input, data2, secret, key + 16, seed));
+    acc[0] ^= read_u64(input, data2).wrapping_add(read_u64(input, data2 + 8));
+    acc[1] ^= read_u64(input, data1).wrapping_add(read_u64(input, data1 + 8));
+}
+
+const fn finalize_medium(acc: [u64; 2], len: u64, seed: u64) -> u128 {
+    let low = acc[0].wrapping_add(acc[1]);
+    let high = acc[0]
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(acc[1].wrapping_mul(PRIME64_4))
+        .wrapping_add(len.wrapping_sub(seed).wrapping_mul(PRIME64_2));
+    from_halves(avalanche(low), avalanche(high).wrapping_neg())
+}
+
+const fn hash_17_to_128(secret: &[u8], seed: u64, input: &[u8]) -> u128 {
+    let len = input.len();
+    let mut acc = [(len as u64).wrapping_mul(PRIME64_1), 0];
+    // Pairs the `i`th 16-byte chunk from the front with the `i`th from the back, outermost last.
+    let rounds = (len - 1) / 32;
+    let mut i = rounds + 1;
+    while i > 0 {
+        i -= 1;
+        mix_32(
+            &mut acc,
+            input,
+            16 * i,
+            len - 16 * (i + 1),
+            secret,
+            32 * i,
+            seed,
+        );
+    }
+    finalize_medium(acc, len as u64, seed)
+}
+
+const fn hash_129_to_240(secret: &[u8], seed: u64, input: &[u8]) -> u128 {
+    let len = input.len();
+    let mut acc = [(len as u64).wrapping_mul(PRIME64_1), 0];
+    let mut i = 0;
+    while i < 4 {
+        mix_32(&mut acc, input, 32 * i, 32 * i + 16, secret, 32 * i, seed);
+        i += 1;
+    }
+    acc = [avalanche(acc[0]), avalanche(acc[1])];
+
+    let rounds = len / 32;
+    while i < rounds {
+        mix_32(
+            &mut acc,
+            input,
+            32 * i,
+            32 * i + 16,
+            secret,
+            32 * (i - 4) + 3,
+            seed,
+        );
+        i += 1;
+    }
+    // The last 32 bytes, halves swapped, with the seed negated.
+    mix_32(
+        &mut acc,
+        input,
+        len - 16,
+        len - 32,
+        secret,
+        103,
+        seed.wrapping_neg(),
+    );
+    finalize_medium(acc, len as u64, seed)
+}
+
+/// The default secret with the seed mixed in, as used for inputs longer than [`MID_SIZE_MAX`].
+const fn derive_secret(seed: u64) -> [u8; SECRET_LEN] {
+    let mut secret = DEFAULT_SECRET;
+    let mut offset = 0;
+    while offset < SECRET_LEN {
+        let low = read_u64(&secret, offset).wrapping_add(seed).to_le_bytes();
+        let high = read_u64(&secret, offset + 8)
+            .wrapping_sub(seed)
+            .to_le_bytes();
+        let mut j = 0;
+        while j < 8 {
+            secret[offset + j] = low[j];
+            secret[offset + 8 + j] = high[j];
+            j += 1;
+        }
+        offset += 16;
+    }
+    secret
+}
+
+/// Accumulates one 64-byte stripe of input at `data` with the secret at `key`.
+const fn accumulate(acc: &mut [u64; 8], input: &[u8], data: usize, secret: &[u8], key: usize) {
+    let mut i = 0;
+    while i < 8 {
+        let value = read_u64(input, data + 8 * i);
+        let keyed = value ^ read_u64(secret, key + 8 * i);
+        acc[i ^ 1] = acc[i ^ 1].wrapping_add(value);
+        acc[i] = acc[i].wrapping_add((keyed & 0xFFFF_FFFF).wrapping_mul(keyed >> 32));
+        i += 1;
+    }
+}
+
+const fn scramble(acc: &mut [u64; 8], secret: &[u8]) {
+    let key = SECRET_LEN - STRIPE_LEN;
+    let mut i = 0;
+    while i < 8 {
+        let mut value = acc[i];
+        value ^= value >> 47;
+        value ^= read_u64(secret, key + 8 * i);
+        acc[i] = value.wrapping_mul(PRIME32_1);
+        i += 1;
+    }
+}
+
+const fn merge_accumulators(acc: &[u64; 8], secret: &[u8], key: usize, start: u64) -> u64 {
+    let mut result = start;
+    let mut i = 0;
+    while i < 4 {
+        result = result.wrapping_add(mul_fold(
+            acc[2 * i] ^ read_u64(secret, key + 16 * i),
+            acc[2 * i + 1] ^ read_u64(secret, key + 16 * i + 8),
+        ));
+        i += 1;
+    }
+    avalanche(result)
+}
+
+const fn hash_large(secret: &[u8; SECRET_LEN], input: &[u8]) -> u128 {
+    let len = input.len();
+    let mut acc = [
+        PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5, PRIME32_1,
+    ];
+
+    // Every full block except the last, which is processed without a scramble even when full.
+    let full_blocks = (len - 1) / BLOCK_LEN;
+    let mut block = 0;
+    while block < full_blocks {
+        let mut stripe = 0;
+        while stripe < STRIPES_PER_BLOCK {
+            accumulate(
+                &mut acc,
+                input,
+                block * BLOCK_LEN + stripe * STRIPE_LEN,
+                secret,
+                8 * stripe,
+            );
+            stripe += 1;
+        }
+        scramble(&mut acc, secret);
+        block += 1;
+    }
+
+    // The full stripes of the last block, except its last stripe even when full.
+    let last_block = full_blocks * BLOCK_LEN;
+    let stripes = (len - last_block - 1) / STRIPE_LEN;
+    let mut stripe = 0;
+    while stripe < stripes {
+        accumulate(
+            &mut acc,
+            input,
+            last_block + stripe * STRIPE_LEN,
+            secret,
+            8 * stripe,
+        );
+        stripe += 1;
+    }
+    // The last 64 bytes of input, which may overlap the stripes before them.
+    accumulate(
+        &mut acc,
+        input,
+        len - STRIPE_LEN,
+        secret,
+        SECRET_LEN - STRIPE_LEN - 7,
+    );
+
+    let len = len as u64;
+    let low = merge_accumulators(&acc, secret, 11, len.wrapping_mul(PRIME64_1));
+    let high = merge_accumulators(
+        &acc,
+        secret,
+        SECRET_LEN - STRIPE_LEN - 11,
+        !len.wrapping_mul(PRIME64_2),
+    );
+    from_halves(low, high)
+}
+
+/// The [`Fingerprint`](crate::hashing::Fingerprint) of the bytes of a string, computed at compile
+/// time with [`xxh3_128`](crate::hashing::xxh3_128). The argument can be any constant `&str`
+/// expression, including `concat!(...)`.
+#[macro_export]
+macro_rules! const_fingerprint {
+    ($s:expr) => {
+        const {
+            $crate::hashing::Fingerprint::new($crate::hashing::xxh3_128(
+                ::core::primitive::str::as_bytes($s),
+            ))
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+
+    use twox_hash::XxHash3_128;
+
+    use super::*;
+    use crate::hashing::{Fingerprint, Hasher128, Xxh3Hasher128};
+
+    fn sample(len: usize) -> Vec<u8> {
+        // A simple LCG, so every byte position gets a different value.
+        let mut state = 0x1234_5678_u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_twox_hash_for_every_length_range() {
+        let data = sample(4 * BLOCK_LEN + 100);
+        // Every length up to a few blocks, which covers every code path and block boundary.
+        for len in 0..=(2 * BLOCK_LEN + 2 * STRIPE_LEN) {
+            let input = &data[..len];
+            assert_eq!(xxh3_128(input), XxHash3_128::oneshot(input), "length {len}");
+            for seed in [1, 0xdead_beef_cafe_f00d] {
+                assert_eq!(
+                    xxh3_128_with_seed(seed, input),
+                    XxHash3_128::oneshot_with_seed(seed, input),
+                    "length {len}, seed {seed:#x}"
+                );
+            }
+        }
+        assert_eq!(xxh3_128(&data), XxHash3_128::oneshot(&data));
+    }
+
+    #[test]
+    fn matches_the_streaming_hasher() {
+        let data = sample(1000);
+        let mut hasher = Xxh3Hasher128::default();
+        hasher.write(&data);
+        assert_eq!(xxh3_128(&data), hasher.finish_u128());
+    }
+
+    #[test]
+    fn evaluates_at_compile_time() {
+        const DIGEST: u128 = xxh3_128(b"hello");
+        const ID: Fingerprint = crate::const_fingerprint!(concat!("hel", "lo"));
+        assert_eq!(DIGEST, XxHash3_128::oneshot(b"hello"));
+        assert_eq!(ID, Fingerprint::new(DIGEST));
+    }
+}
@@ -0,0 +1,287 @@
+/*!
+
+# Fingerprints
+
+A 128-bit hash used as an _identity_ deserves its own type. A bare `u128` can be confused with a
+count, an ID, or a hash computed by a different hasher, and it doesn't know how to print itself in
+the form everybody expects (hex). [`Fingerprint`] fixes that and adds the handful of operations
+you actually need:
+
+- [`Fingerprint::combine`] folds two fingerprints into one in an order-sensitive way, for
+  fingerprinting sequences and composite keys.
+- [`Fingerprint::combine_unordered`] does the same in an order-insensitive way, for fingerprinting
+  sets. It's wrapping addition, not XOR. XOR is also commutative, but `x ^ x == 0`, so every set
+  containing a duplicated element fingerprint would collide with the empty set.
+
+A `Fingerprint` is already a uniformly distributed hash, so hashing it _again_ to put it in a hash
+map is wasted work. [`FingerprintHasher`] is an identity hasher that passes it straight through,
+and [`FingerprintMap`](super::FingerprintMap) and [`FingerprintSet`](super::FingerprintSet) use it.
+
+With the `serde` feature, a `Fingerprint` serializes as its 32-digit hex string in human-readable
+formats like JSON (where a `u128` would lose precision in many consumers) and as a `u128` otherwise.
+
+*/
+
+use core::{
+    fmt::{Debug, Display, Formatter},
+    hash::{BuildHasherDefault, Hash, Hasher},
+    str::FromStr,
+};
+
+use twox_hash::XxHash3_128;
+
+use super::{BuildHasher128, Hasher128, one_shot_128};
+
+/// A 128-bit hash that identifies a value.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(u128);
+
+impl Fingerprint {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn new(value: u128) -> Self {
+        Self(value)
+    }
+
+    /// The fingerprint of `value`, i.e. [`one_shot_128`].
+    pub fn of<T: Hash>(value: &T) -> Self {
+        Self(one_shot_128(value))
+    }
+
+    pub const fn as_u128(self) -> u128 {
+        self.0
+    }
+
+    /// The low 64 bits. This is what a 64-bit hash table should use as the hash.
+    pub const fn as_u64(self) -> u64 {
+        self.0 as u64
+    }
+
+    /// The `(low, high)` 64-bit halves, e.g. for double hashing.
+    pub const fn halves(self) -> (u64, u64) {
+        (self.0 as u64, (self.0 >> 64) as u64)
+    }
+
+    /// Combines two fingerprints such that `a.combine(b) != b.combine(a)` (with overwhelming
+    /// probability).
+    pub fn combine(self, other: Self) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[..16].copy_from_slice(&self.0.to_le_bytes());
+        bytes[16..].copy_from_slice(&other.0.to_le_bytes());
+        Self(XxHash3_128::oneshot(&bytes))
+    }
+
+    /// Combines two fingerprints such that `a.combine_unordered(b) == b.combine_unordered(a)`. It
+    /// is also associative, so any number of fingerprints can be combined in any order.
+    pub const fn combine_unordered(self, other: Self) -> Self {
+        Self(self.0.wrapping_add(other.0))
+    }
+}
+
+impl From<u128> for Fingerprint {
+    fn from(value: u128) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Fingerprint> for u128 {
+    fn from(value: Fingerprint) -> Self {
+        value.0
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl Debug for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Fingerprint({self})")
+    }
+}
+
+/// The error returned when parsing a [`Fingerprint`] from a string fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseFingerprintError {
+    /// A fingerprint is exactly 32 hex digits. Holds the length we got instead.
+    WrongLength(usize),
+    /// The string had the right length but contained this non-hex character.
+    InvalidDigit(char),
+}
+
+impl Display for ParseFingerprintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::WrongLength(len) => {
+                write!(f, "a fingerprint is 32 hex digits, got {len} characters")
+            }
+            Self::InvalidDigit(c) => write!(f, "invalid hex digit {c:?} in fingerprint"),
+        }
+    }
+}
+
+impl core::error::Error for ParseFingerprintError {}
+
+/// Parses the output of `Display`, i.e. exactly 32 hex digits, either case.
+impl FromStr for Fingerprint {
+    type Err = ParseFingerprintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 32 {
+            return Err(ParseFingerprintError::WrongLength(s.len()));
+        }
+        // Checking the digits ourselves also rules out the leading `+` that `from_str_radix`
+        // would tolerate.
+        if let Some(c) = s.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(ParseFingerprintError::InvalidDigit(c));
+        }
+        Ok(Self(u128::from_str_radix(s, 16).unwrap()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Fingerprint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u128(self.0)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Fingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = <alloc::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+            s.parse().map_err(serde::de::Error::custom)
+        } else {
+            u128::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+/// An identity [`Hasher`] for keys that are already hashes. Writing a `u128` (which is what
+/// `Fingerprint`'s `Hash` impl does) just stores it. Anything else is hashed with XXH3 so the
+/// hasher is still correct, just not free, if it's used with other keys by mistake.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct FingerprintHasher(u128);
+
+impl Hasher for FingerprintHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = XxHash3_128::oneshot_with_seed(self.0 as u64, bytes);
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.0 = value;
+    }
+
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+}
+
+impl Hasher128 for FingerprintHasher {
+    fn finish_u128(&self) -> u128 {
+        self.0
+    }
+}
+
+/// Builds [`FingerprintHasher`]s.
+pub type BuildFingerprintHasher = BuildHasherDefault<FingerprintHasher>;
+
+impl BuildHasher128 for BuildFingerprintHasher {}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasher;
+
+    use super::*;
+    use crate::hashing::FingerprintMap;
+
+    #[test]
+    fn display_and_parse_round_trip() {
+        let fingerprint = Fingerprint::new(0x0123_4567_89ab_cdef_0011_2233_4455_6677);
+        let text = fingerprint.to_string();
+        assert_eq!(text, "0123456789abcdef0011223344556677");
+        assert_eq!(text.parse::<Fingerprint>(), Ok(fingerprint));
+        assert_eq!(text.to_uppercase().parse::<Fingerprint>(), Ok(fingerprint));
+        assert_eq!(Fingerprint::ZERO.to_string(), "0".repeat(32));
+        assert_eq!(
+            format!("{fingerprint:?}"),
+            "Fingerprint(0123456789abcdef0011223344556677)"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "abc".parse::<Fingerprint>(),
+            Err(ParseFingerprintError::WrongLength(3))
+        );
+        assert_eq!(
+            "g".repeat(32).parse::<Fingerprint>(),
+            Err(ParseFingerprintError::InvalidDigit('g'))
+        );
+        assert_eq!(
+            format!("+{}", "0".repeat(31)).parse::<Fingerprint>(),
+            Err(ParseFingerprintError::InvalidDigit('+'))
+        );
+    }
+
+    #[test]
+    fn ordering_follows_the_integer() {
+        assert!(Fingerprint::new(1) < Fingerprint::new(2));
+        assert!(Fingerprint::new(u128::MAX) > Fingerprint::ZERO);
+    }
+
+    #[test]
+    fn combine_is_order_sensitive() {
+        let a = Fingerprint::of(&"a");
+        let b = Fingerprint::of(&"b");
+        assert_ne!(a.combine(b), b.combine(a));
+        assert_eq!(a.combine(b), a.combine(b));
+    }
+
+    #[test]
+    fn combine_unordered_is_order_insensitive() {
+        let [a, b, c] = [&"a", &"b", &"c"].map(Fingerprint::of);
+        assert_eq!(a.combine_unordered(b), b.combine_unordered(a));
+        assert_eq!(
+            a.combine_unordered(b).combine_unordered(c),
+            c.combine_unordered(a).combine_unordered(b)
+        );
+        // Unlike XOR, a duplicated element doesn't cancel out.
+        assert_ne!(a.combine_unordered(a), Fingerprint::ZERO);
+    }
+
+    #[test]
+    fn identity_hasher_passes_fingerprints_through() {
+        let fingerprint = Fingerprint::of(&"hello");
+        let build_hasher = BuildFingerprintHasher::default();
+        assert_eq!(build_hasher.hash_one(fingerprint), fingerprint.as_u64());
+        assert_eq!(
+            build_hasher.hash_one_128(&fingerprint),
+            fingerprint.as_u128()
+        );
+
+        let mut map: FingerprintMap<&str> = FingerprintMap::default();
+        map.insert(fingerprint, "hello");
+        assert_eq!(map.get(&fingerprint), Some(&"hello"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_uses_hex_for_human_readable_formats() {
+        let fingerprint = Fingerprint::new(0xff);
+        let json = serde_json::to_string(&fingerprint).unwrap();
+        assert_eq!(json, format!("\"{fingerprint}\""));
+        assert_eq!(
+            serde_json::from_str::<Fingerprint>(&json).unwrap(),
+            fingerprint
+        );
+    }
+}
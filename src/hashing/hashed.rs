@@ -0,0 +1,181 @@
+/*!
+
+# Cached Hashes
+
+Hashing a large key is not free, and a hash table hashes its keys more often than you might think:
+once per lookup, once per insert, and once per stored key every time the table grows. If the table
+identifies keys by their 128-bit hash instead of comparing them, as
+[`Index`](crate::type_erasure::type_erased_api::Index) does, it also hashes every stored key it
+probes along the way.
+
+[`Hashed<T>`] computes the [`Fingerprint`] of a value once and carries it around with the value.
+Its `Hash` impl writes only the cached fingerprint, so in a map built with
+[`BuildFingerprintHasher`](super::BuildFingerprintHasher), which passes a written `u128` straight
+through, hashing a `Hashed<T>` costs nothing at all. Equality checks the fingerprints first and
+only compares the values when they match, which makes unequal keys cheap to tell apart too.
+
+The cached fingerprint is only correct as long as the value doesn't change, which is why there's no
+`DerefMut`. A value with interior mutability that affects its `Hash` impl will go stale, just as it
+would inside any hash map.
+
+*/
+
+use core::{
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use super::{BuildHasher128, Fingerprint};
+
+/// A value together with its cached [`Fingerprint`].
+#[derive(Clone)]
+pub struct Hashed<T> {
+    fingerprint: Fingerprint,
+    value: T,
+}
+
+impl<T: Hash> Hashed<T> {
+    /// Wraps `value` with its fingerprint as computed by [`one_shot_128`](super::one_shot_128).
+    pub fn new(value: T) -> Self {
+        Self {
+            fingerprint: Fingerprint::of(&value),
+            value,
+        }
+    }
+
+    /// Wraps `value` with its fingerprint as computed by `build_hasher`.
+    pub fn with_hasher<S: BuildHasher128>(value: T, build_hasher: &S) -> Self {
+        Self {
+            fingerprint: Fingerprint::new(build_hasher.hash_one_128(&value)),
+            value,
+        }
+    }
+}
+
+impl<T> Hashed<T> {
+    /// Wraps `value` with a fingerprint computed elsewhere. It's up to the caller that it's the
+    /// right one.
+    pub const fn from_parts(fingerprint: Fingerprint, value: T) -> Self {
+        Self { fingerprint, value }
+    }
+
+    pub const fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    pub const fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    pub fn into_parts(self) -> (Fingerprint, T) {
+        (self.fingerprint, self.value)
+    }
+}
+
+impl<T> Deref for Hashed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> AsRef<T> for Hashed<T> {
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Hash> From<T> for Hashed<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Writes only the cached fingerprint.
+impl<T> Hash for Hashed<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fingerprint.hash(state);
+    }
+}
+
+impl<T: PartialEq> PartialEq for Hashed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint && self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Hashed<T> {}
+
+impl<T: Debug> Debug for Hashed<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Hashed")
+            .field("fingerprint", &self.fingerprint)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, hash::BuildHasher};
+
+    use super::*;
+    use crate::hashing::{BuildFingerprintHasher, Xxh3BuildHasher, one_shot_128};
+
+    /// Counts how many times it has been hashed.
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl Hash for Counted<'_> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.set(self.0.get() + 1);
+            state.write_u8(0);
+        }
+    }
+
+    #[test]
+    fn caches_the_fingerprint() {
+        let hashed = Hashed::new("hello".to_string());
+        assert_eq!(
+            hashed.fingerprint().as_u128(),
+            one_shot_128(&"hello".to_string())
+        );
+        assert_eq!(*hashed, "hello");
+
+        let seeded = Hashed::with_hasher("hello".to_string(), &Xxh3BuildHasher::new(1));
+        assert_ne!(seeded.fingerprint(), hashed.fingerprint());
+    }
+
+    #[test]
+    fn pass_through_hasher_never_rehashes_the_value() {
+        let count = Cell::new(0);
+        let hashed = Hashed::new(Counted(&count));
+        assert_eq!(count.get(), 1);
+
+        let build_hasher = BuildFingerprintHasher::default();
+        assert_eq!(
+            build_hasher.hash_one(&hashed),
+            hashed.fingerprint().as_u64()
+        );
+        assert_eq!(
+            build_hasher.hash_one_128(&hashed),
+            hashed.fingerprint().as_u128()
+        );
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn works_as_a_map_key() {
+        let mut map = hashbrown::HashMap::with_hasher(BuildFingerprintHasher::default());
+        map.insert(Hashed::new("a"), 1);
+        map.insert(Hashed::new("b"), 2);
+        assert_eq!(map.get(&Hashed::new("a")), Some(&1));
+        assert_eq!(map.insert(Hashed::new("b"), 3), Some(2));
+        assert_eq!(map.len(), 2);
+    }
+}
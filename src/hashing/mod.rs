@@ -0,0 +1,355 @@
+/*!
+
+# Hiding the Backing Implementation of Algorithms and Data Structures
+
+Split up your hashing algorithm and data structure use cases in your own module so you can switch out the backing
+implementations easily.
+
+Whenever you have reasons to use different hash functions and alternative hash set / hash
+map implementations, define them in their own module, and always draw the type from your
+module in the rest of the codebase. Slicing up your use cases in your own module makes it
+trivial to switch out the backing implementations for those specific use cases.
+
+This is one of the most boring, bog-standard software engineering practices that exist,
+but for some reason it's amazingly common to ignore it for hashing algorithms and related
+data structures. Other categories that somehow escape this standard treatment include:
+
+- thread pools and executors
+- time and scheduling
+- spans, source code locations (in parsers)
+- serialization and deserialization
+- interning, e.g. for strings (`string_cache`, `ustr`)
+- numeric libraries
+- `smallvec`, `tiny_vec`, etc.
+
+We are surprisingly happy to spew the specific backing implementation details across
+the codebase for these use cases. On the other hand, we are curiously really good at
+hiding backing implementation details from the rest of the codebase for other things like:
+
+- logging and tracing (thanks to the `log` crate)
+- random number generation (thanks to the `rand` crate)
+
+Error types could arguably be put on both lists. Error handling is really difficult in any language and at its best
+rises to an art form. Others have written about it better than I ever could:
+
+- [Designing error types in Rust — Matthieu
+  M.](https://mmapped.blog/posts/12-rust-error-handling.html) Discusses
+  the distinction between library and application error design, and the
+  tradeoffs between defining specific enums versus using catch-all wrappers.
+- [Error type design — Rust Error Handling Project Guide
+  (Niko Matsakis et al.).](https://nrc.github.io/error-docs/error-design/error-type-design.html)
+  Explores different error-type strategies in Rust, including concrete enums,
+  single-struct errors, and trait-object approaches, and when each is appropriate.
+- [Structuring and handling errors in 2020 — Nick Groenen.](https://nick.groenen.me/posts/rust-error-handling/)
+  Examines modern idioms using crates like `anyhow` and `thiserror`, and how design goals differ for applications
+  versus reusable libraries.
+
+*/
+
+pub mod collections;
+pub mod const_xxh3;
+pub mod fingerprint;
+pub mod hashed;
+pub mod partition;
+#[cfg(feature = "std")]
+pub mod sketch;
+pub mod stable_hash;
+#[cfg(feature = "std")]
+pub mod streaming;
+pub mod trace;
+pub mod unordered;
+
+use core::hash::{BuildHasher, Hash, Hasher};
+
+use twox_hash::XxHash3_128;
+
+pub use self::{
+    collections::*,
+    const_xxh3::{xxh3_128, xxh3_128_with_seed},
+    fingerprint::{BuildFingerprintHasher, Fingerprint, FingerprintHasher, ParseFingerprintError},
+    hashed::Hashed,
+    partition::{JumpHash, Partitioner, RendezvousHash},
+    stable_hash::{StableHash, stable_one_shot_128},
+    trace::{HashTrace, TracingHasher, trace_hash},
+    unordered::{UnorderedHashMap, UnorderedHashSet, unordered_hash_128},
+};
+#[cfg(feature = "std")]
+pub use self::{
+    sketch::{BloomFilter, CountMinSketch, HyperLogLog, IncompatibleSketches},
+    streaming::{hash_file_128, hash_reader_128, hash_reader_128_with_buffer},
+};
+
+/// The seed used by [`Xxh3Hasher128::default()`], [`Xxh3BuildHasher::default()`], and the unseeded
+/// `one_shot_*` helpers.
+pub const DEFAULT_SEED: u64 = 0;
+
+/// A [`Hasher`] that can also produce a 128-bit digest. `Hasher::finish` is stuck with `u64`.
+pub trait Hasher128: Hasher {
+    /// Returns the 128-bit digest of the bytes written so far without consuming the state.
+    fn finish_u128(&self) -> u128;
+}
+
+/// A [`BuildHasher`] whose hashers produce 128-bit digests. This is the 128-bit analog of
+/// `BuildHasher::hash_one`, and it's what [`Index`](crate::type_erasure::type_erased_api::Index)
+/// is generic over.
+pub trait BuildHasher128: BuildHasher<Hasher: Hasher128> {
+    /// Calculates the 128-bit hash of a single value.
+    fn hash_one_128<T: Hash + ?Sized>(&self, value: &T) -> u128 {
+        let mut h = self.build_hasher();
+        value.hash(&mut h);
+        h.finish_u128()
+    }
+}
+
+pub struct Xxh3Hasher128(XxHash3_128);
+
+impl Default for Xxh3Hasher128 {
+    fn default() -> Self {
+        Self::with_seed(DEFAULT_SEED)
+    }
+}
+
+impl Xxh3Hasher128 {
+    /// Hashers with different seeds are effectively different hash functions, which is what you
+    /// want for domain separation.
+    pub fn with_seed(seed: u64) -> Self {
+        Self(XxHash3_128::with_seed(seed))
+    }
+}
+
+impl Hasher for Xxh3Hasher128 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes); // stream bytes, no allocation
+    }
+    // Hasher requires a u64 result; return the 64-bit XXH3 if you want,
+    // or the low 64 bits of the 128-bit digest.
+    fn finish(&self) -> u64 {
+        // digest* usually consumes; clone the small state to compute without mutating
+        self.0.finish_128() as u64
+    }
+}
+
+impl Hasher128 for Xxh3Hasher128 {
+    fn finish_u128(&self) -> u128 {
+        self.0.finish_128()
+    }
+}
+
+/// Builds seeded [`Xxh3Hasher128`]s. Use it anywhere a `BuildHasher` is expected:
+///
+/// ```rust
+/// # use rust_patterns::hashing::Xxh3BuildHasher;
+/// let mut map: std::collections::HashMap<&str, u32, _> =
+///     std::collections::HashMap::with_hasher(Xxh3BuildHasher::new(42));
+/// map.insert("answer", 42);
+/// ```
+///
+/// The seed is the whole point. Two hashers built with different seeds disagree about every
+/// value, so hashes computed for different _domains_ (value hashes vs. type hashes, say) can't be
+/// confused with one another even if the hashed bytes happen to coincide. Give each domain its
+/// own `Xxh3BuildHasher`, ideally defined once in a module like this one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Xxh3BuildHasher {
+    seed: u64,
+}
+
+impl Default for Xxh3BuildHasher {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl Xxh3BuildHasher {
+    pub const fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Derives the seed from a human-readable domain name, e.g. `"type-hash"`. Distinct names
+    /// give (with overwhelming probability) distinct seeds.
+    pub fn for_domain(domain: &str) -> Self {
+        Self::new(twox_hash::XxHash3_64::oneshot(domain.as_bytes()))
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl BuildHasher for Xxh3BuildHasher {
+    type Hasher = Xxh3Hasher128;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        Xxh3Hasher128::with_seed(self.seed)
+    }
+}
+
+impl BuildHasher128 for Xxh3BuildHasher {}
+
+/// A deliberately weak [`BuildHasher128`] that keeps only the low `bits` bits of the XXH3 digest. With `bits = 0`,
+/// every value collides with every other. It exists to exercise code that has to cope with (or detect) collisions,
+/// like the collision audit in [`Index`](crate::type_erasure::type_erased_api::Index). Never use it for real; it's only
+/// built for tests and with the `collision-audit` feature.
+#[cfg(any(test, feature = "collision-audit"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TruncatingBuildHasher {
+    bits: u32,
+}
+
+#[cfg(any(test, feature = "collision-audit"))]
+impl TruncatingBuildHasher {
+    pub const fn new(bits: u32) -> Self {
+        Self { bits }
+    }
+}
+
+#[cfg(any(test, feature = "collision-audit"))]
+impl BuildHasher for TruncatingBuildHasher {
+    type Hasher = TruncatingHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        TruncatingHasher {
+            inner: Xxh3Hasher128::default(),
+            mask: u128::MAX.checked_shr(128 - self.bits.min(128)).unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "collision-audit"))]
+impl BuildHasher128 for TruncatingBuildHasher {}
+
+/// The hasher built by [`TruncatingBuildHasher`].
+#[cfg(any(test, feature = "collision-audit"))]
+pub struct TruncatingHasher {
+    inner: Xxh3Hasher128,
+    mask: u128,
+}
+
+#[cfg(any(test, feature = "collision-audit"))]
+impl Hasher for TruncatingHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.inner.write(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.finish_u128() as u64
+    }
+}
+
+#[cfg(any(test, feature = "collision-audit"))]
+impl Hasher128 for TruncatingHasher {
+    fn finish_u128(&self) -> u128 {
+        self.inner.finish_u128() & self.mask
+    }
+}
+
+// Helper for any T: Hash
+pub fn one_shot_128<T: Hash>(value: &T) -> u128 {
+    one_shot_128_with_seed(value, DEFAULT_SEED)
+}
+
+// Helper for any T: Hash
+pub fn one_shot_64<T: Hash>(value: &T) -> u64 {
+    one_shot_64_with_seed(value, DEFAULT_SEED)
+}
+
+/// Seeded version of [`one_shot_128`].
+pub fn one_shot_128_with_seed<T: Hash>(value: &T, seed: u64) -> u128 {
+    Xxh3BuildHasher::new(seed).hash_one_128(value)
+}
+
+/// Seeded version of [`one_shot_64`].
+pub fn one_shot_64_with_seed<T: Hash>(value: &T, seed: u64) -> u64 {
+    Xxh3BuildHasher::new(seed).hash_one(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_strings() {
+        let a = one_shot_128(&"hello");
+        let b = one_shot_128(&"hello");
+        let c = one_shot_128(&"world");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn hashes_structs() {
+        #[derive(Hash)]
+        struct S {
+            x: u32,
+            y: String,
+        }
+        let h1 = one_shot_128(&S {
+            x: 1,
+            y: "a".into(),
+        });
+        let h2 = one_shot_128(&S {
+            x: 1,
+            y: "a".into(),
+        });
+        assert_eq!(h1, h2);
+    }
+
+    #[test]
+    fn default_seed_matches_unseeded_helpers() {
+        assert_eq!(
+            one_shot_128(&"hello"),
+            one_shot_128_with_seed(&"hello", DEFAULT_SEED)
+        );
+        assert_eq!(
+            one_shot_64(&"hello"),
+            one_shot_64_with_seed(&"hello", DEFAULT_SEED)
+        );
+        assert_eq!(
+            one_shot_128(&"hello"),
+            Xxh3BuildHasher::default().hash_one_128(&"hello")
+        );
+    }
+
+    #[test]
+    fn seeds_separate_domains() {
+        let values = Xxh3BuildHasher::for_domain("value-hash");
+        let types = Xxh3BuildHasher::for_domain("type-hash");
+        assert_ne!(values.seed(), types.seed());
+        assert_ne!(values.hash_one_128(&"hello"), types.hash_one_128(&"hello"));
+        assert_ne!(
+            one_shot_128_with_seed(&"hello", 1),
+            one_shot_128_with_seed(&"hello", 2)
+        );
+    }
+
+    #[test]
+    fn truncating_hasher_forces_collisions() {
+        assert_eq!(TruncatingBuildHasher::new(0).hash_one_128("a"), 0);
+        assert_eq!(
+            TruncatingBuildHasher::new(8).hash_one_128("a"),
+            one_shot_128(&"a") & 0xff
+        );
+        assert_eq!(
+            TruncatingBuildHasher::new(128).hash_one_128("a"),
+            one_shot_128(&"a")
+        );
+    }
+
+    #[test]
+    fn build_hasher_plugs_into_maps() {
+        let build_hasher = Xxh3BuildHasher::new(7);
+
+        let mut std_map = std::collections::HashMap::with_hasher(build_hasher);
+        std_map.insert("a", 1);
+        assert_eq!(std_map.get("a"), Some(&1));
+
+        let mut hb_map = hashbrown::HashMap::with_hasher(build_hasher);
+        hb_map.insert("a", 1);
+        assert_eq!(hb_map.get("a"), Some(&1));
+
+        // The low 64 bits of the 128-bit digest are what the maps see.
+        assert_eq!(
+            build_hasher.hash_one("a"),
+            build_hasher.hash_one_128("a") as u64
+        );
+    }
+}
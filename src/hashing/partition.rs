@@ -0,0 +1,297 @@
+/*!
+
+# Partitioning
+
+Spreading keys (entity IDs, say) across `n` workers or shards with `hash(key) % n` balances the
+load fine, but the moment `n` changes, almost every key lands somewhere else: going from `n` to
+`n + 1` shards moves a fraction `n / (n + 1)` of the keys. If moving a key means moving its data,
+that's a full reshuffle for every resize.
+
+The two schemes here move only the keys they have to, about `1 / (n + 1)` of them when a shard is
+added, and only the removed shard's keys when one is removed:
+
+- [`JumpHash`] (Lamping and Veach, "A Fast, Minimal Memory, Consistent Hash Algorithm") needs no
+  memory at all and is very fast, but shards are numbered `0..n`, and only the _last_ shard can be
+  removed. Use it for shards that are added and removed in order, like replicas or threads.
+- [`RendezvousHash`] (highest random weight hashing) assigns each key to the node with the highest
+  `hash(key, node)`. It costs `O(n)` per lookup but works for arbitrary, named nodes, any of which
+  can be removed. Use it for a small set of named workers that come and go.
+
+Both hash keys with a seeded [`Xxh3BuildHasher`], so different seeds give independent
+partitionings, and both implement the [`Partitioner`] trait.
+
+*/
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use super::{BuildHasher128, Fingerprint, Xxh3BuildHasher};
+
+/// Assigns keys to one of a number of shards.
+pub trait Partitioner {
+    /// The number of shards keys are assigned to.
+    fn shard_count(&self) -> usize;
+
+    /// The shard `key` belongs to, in `0..shard_count()`.
+    fn shard_for<K: Hash + ?Sized>(&self, key: &K) -> usize;
+
+    /// Splits `keys` into one bucket per shard.
+    fn partition<K: Hash, I: IntoIterator<Item = K>>(&self, keys: I) -> Vec<Vec<K>> {
+        let mut shards: Vec<Vec<K>> = (0..self.shard_count()).map(|_| Vec::new()).collect();
+        for key in keys {
+            shards[self.shard_for(&key)].push(key);
+        }
+        shards
+    }
+}
+
+/// Jump consistent hashing over shards numbered `0..shard_count`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct JumpHash {
+    shard_count: usize,
+    build_hasher: Xxh3BuildHasher,
+}
+
+impl JumpHash {
+    pub fn new(shard_count: usize) -> Self {
+        Self::with_seed(shard_count, super::DEFAULT_SEED)
+    }
+
+    pub fn with_seed(shard_count: usize, seed: u64) -> Self {
+        assert!(shard_count > 0, "there must be at least one shard");
+        Self {
+            shard_count,
+            build_hasher: Xxh3BuildHasher::new(seed),
+        }
+    }
+
+    /// Adds or removes shards at the end of the range. Only keys on removed shards, or keys that
+    /// now belong to an added shard, change shards.
+    pub fn set_shard_count(&mut self, shard_count: usize) {
+        assert!(shard_count > 0, "there must be at least one shard");
+        self.shard_count = shard_count;
+    }
+}
+
+impl Partitioner for JumpHash {
+    fn shard_count(&self) -> usize {
+        self.shard_count
+    }
+
+    fn shard_for<K: Hash + ?Sized>(&self, key: &K) -> usize {
+        let mut key = self.build_hasher.hash_one_128(key) as u64;
+        // `b` is the shard the key is in so far, `j` the next shard it would jump to.
+        let mut b: i64 = -1;
+        let mut j: i64 = 0;
+        while j < self.shard_count as i64 {
+            b = j;
+            key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+            j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+        }
+        b as usize
+    }
+}
+
+/// Rendezvous (highest random weight) hashing over a set of named nodes.
+#[derive(Clone, Debug)]
+pub struct RendezvousHash<N> {
+    /// Each node with its precomputed hash.
+    nodes: Vec<(N, Fingerprint)>,
+    build_hasher: Xxh3BuildHasher,
+}
+
+impl<N: Hash + Eq> RendezvousHash<N> {
+    pub fn new(nodes: impl IntoIterator<Item = N>) -> Self {
+        Self::with_seed(nodes, super::DEFAULT_SEED)
+    }
+
+    pub fn with_seed(nodes: impl IntoIterator<Item = N>, seed: u64) -> Self {
+        let mut rendezvous = Self {
+            nodes: Vec::new(),
+            build_hasher: Xxh3BuildHasher::new(seed),
+        };
+        for node in nodes {
+            rendezvous.add_node(node);
+        }
+        rendezvous
+    }
+
+    /// Adds `node` if it isn't already present. Returns whether it was added. Only keys that now
+    /// belong to `node` change nodes.
+    pub fn add_node(&mut self, node: N) -> bool {
+        if self.nodes.iter().any(|(existing, _)| *existing == node) {
+            return false;
+        }
+        let hash = Fingerprint::new(self.build_hasher.hash_one_128(&node));
+        self.nodes.push((node, hash));
+        true
+    }
+
+    /// Removes `node` if it's present. Returns whether it was removed. Only keys that belonged to
+    /// `node` change nodes.
+    ///
+    /// Removing a node changes the shard _numbers_ of the nodes after it, so identify nodes by
+    /// [`node_for`](Self::node_for) rather than by [`shard_for`](Partitioner::shard_for) across
+    /// changes.
+    pub fn remove_node(&mut self, node: &N) -> bool {
+        let before = self.nodes.len();
+        self.nodes.retain(|(existing, _)| existing != node);
+        self.nodes.len() != before
+    }
+
+    /// The node `key` belongs to, or `None` if there are no nodes.
+    pub fn node_for<K: Hash + ?Sized>(&self, key: &K) -> Option<&N> {
+        self.shard_index(key).map(|shard| &self.nodes[shard].0)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &N> {
+        self.nodes.iter().map(|(node, _)| node)
+    }
+
+    fn shard_index<K: Hash + ?Sized>(&self, key: &K) -> Option<usize> {
+        let key = Fingerprint::new(self.build_hasher.hash_one_128(key));
+        // Ties are astronomically unlikely, but break them by node order so the result is still
+        // deterministic.
+        self.nodes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, node))| key.combine(*node))
+            .map(|(shard, _)| shard)
+    }
+}
+
+impl<N: Hash + Eq> Partitioner for RendezvousHash<N> {
+    fn shard_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The index of the key's node in [`nodes`](RendezvousHash::nodes). Panics if there are no
+    /// nodes.
+    fn shard_for<K: Hash + ?Sized>(&self, key: &K) -> usize {
+        self.shard_index(key)
+            .expect("a `RendezvousHash` with no nodes can't assign keys")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::one_shot_64;
+
+    const KEYS: u64 = 20_000;
+
+    /// The fraction of keys whose assignment differs between `before` and `after`.
+    fn moved_fraction<T: PartialEq>(before: impl Fn(u64) -> T, after: impl Fn(u64) -> T) -> f64 {
+        let moved = (0..KEYS).filter(|key| before(*key) != after(*key)).count();
+        moved as f64 / KEYS as f64
+    }
+
+    fn assert_balanced(partitioner: &impl Partitioner) {
+        let shards = partitioner.partition(0..KEYS);
+        let mean = KEYS as f64 / shards.len() as f64;
+        for shard in &shards {
+            // Shard sizes are roughly Poisson distributed, so allow five standard deviations.
+            let deviation = (shard.len() as f64 - mean).abs();
+            assert!(
+                deviation < 5.0 * mean.sqrt(),
+                "shard sizes {:?}",
+                shards.iter().map(Vec::len).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn modulo_hashing_moves_almost_everything() {
+        // The baseline the other schemes are measured against.
+        let moved = moved_fraction(|key| one_shot_64(&key) % 10, |key| one_shot_64(&key) % 11);
+        assert!(moved > 0.85, "moved {moved}");
+    }
+
+    #[test]
+    fn jump_hash_is_balanced() {
+        for shards in [1, 7, 10, 32] {
+            assert_balanced(&JumpHash::new(shards));
+        }
+    }
+
+    #[test]
+    fn jump_hash_moves_few_keys_when_resized() {
+        let ten = JumpHash::new(10);
+        let mut eleven = ten;
+        eleven.set_shard_count(11);
+
+        // Adding a shard moves about 1/11 of the keys, all of them to the new shard.
+        let moved = moved_fraction(|key| ten.shard_for(&key), |key| eleven.shard_for(&key));
+        assert!((moved - 1.0 / 11.0).abs() < 0.01, "moved {moved}");
+        assert!((0..KEYS).all(
+            |key| ten.shard_for(&key) == eleven.shard_for(&key) || eleven.shard_for(&key) == 10
+        ));
+
+        // Removing it moves exactly those keys back.
+        let moved_back = moved_fraction(|key| eleven.shard_for(&key), |key| ten.shard_for(&key));
+        assert_eq!(moved, moved_back);
+    }
+
+    #[test]
+    fn seeds_give_independent_partitionings() {
+        let a = JumpHash::with_seed(10, 1);
+        let b = JumpHash::with_seed(10, 2);
+        // Independent assignments agree about 1/10 of the time.
+        let moved = moved_fraction(|key| a.shard_for(&key), |key| b.shard_for(&key));
+        assert!((moved - 0.9).abs() < 0.01, "moved {moved}");
+
+        let a = RendezvousHash::with_seed(0..10, 1);
+        let b = RendezvousHash::with_seed(0..10, 2);
+        let moved = moved_fraction(|key| a.shard_for(&key), |key| b.shard_for(&key));
+        assert!((moved - 0.9).abs() < 0.01, "moved {moved}");
+    }
+
+    #[test]
+    fn rendezvous_hash_is_balanced() {
+        assert_balanced(&RendezvousHash::new(["a", "b", "c", "d", "e"]));
+    }
+
+    #[test]
+    fn rendezvous_hash_moves_few_keys_when_nodes_change() {
+        let workers = [
+            "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+        ];
+        let before = RendezvousHash::new(workers);
+
+        // Adding a node only moves keys to it.
+        let mut added = before.clone();
+        assert!(added.add_node("lambda"));
+        assert!(!added.add_node("lambda"));
+        let moved = moved_fraction(
+            |key| before.node_for(&key).copied(),
+            |key| added.node_for(&key).copied(),
+        );
+        assert!((moved - 1.0 / 11.0).abs() < 0.01, "moved {moved}");
+        assert!((0..KEYS).all(|key| {
+            let new = added.node_for(&key);
+            new == before.node_for(&key) || new == Some(&"lambda")
+        }));
+
+        // Removing a node from the middle only moves its own keys.
+        let mut removed = before.clone();
+        assert!(removed.remove_node(&"delta"));
+        assert!(!removed.remove_node(&"delta"));
+        assert_eq!(removed.shard_count(), 9);
+        let moved = moved_fraction(
+            |key| before.node_for(&key).copied(),
+            |key| removed.node_for(&key).copied(),
+        );
+        assert!((moved - 1.0 / 10.0).abs() < 0.01, "moved {moved}");
+        assert!((0..KEYS).all(|key| {
+            let old = before.node_for(&key);
+            old == Some(&"delta") || old == removed.node_for(&key)
+        }));
+    }
+
+    #[test]
+    fn empty_rendezvous_hash_assigns_nothing() {
+        let empty = RendezvousHash::<u32>::new([]);
+        assert_eq!(empty.node_for(&1), None);
+        assert_eq!(empty.shard_count(), 0);
+    }
+}
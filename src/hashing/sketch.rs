@@ -0,0 +1,470 @@
+/*!
+
+# Sketches
+
+Probabilistic data structures answer questions about a stream of values, like "have I seen this
+before?", "how many distinct values are there?", and "how often does this value occur?", in a fixed
+amount of memory, at the cost of a bounded error. They're the way to size up a column before
+committing to building an [`Index`](crate::type_erasure::type_erased_api::Index) for it.
+
+- [`BloomFilter<T>`]: set membership with no false negatives and a tunable false positive rate.
+- [`HyperLogLog`]: the number of distinct values, with a relative standard error of
+  `1.04 / sqrt(2^precision)`.
+- [`CountMinSketch<T>`]: the frequency of each value, never underestimated, and overestimated by
+  at most `epsilon` times the total count with probability `1 - delta`.
+
+All three need several independent hashes of each value. Rather than hash the value several times,
+they take its 128-bit [`Fingerprint`], split it into two 64-bit halves `h1` and `h2`, and use
+`h1 + i * h2` as the `i`th hash. Kirsch and Mitzenmacher showed that this "double hashing" loses
+nothing asymptotically over truly independent hashes.
+
+Every structure has `*_fingerprint` variants of its methods that take the fingerprint directly, so
+you can feed it the [`HashValue`](crate::type_erasure::type_erased_api::HashValue)s of the
+type-erased API without ever having the values. Two sketches with the same parameters can be merged
+into the sketch of the combined stream, so a column can be sketched in parallel.
+
+*/
+
+use std::{
+    fmt::{Display, Formatter},
+    hash::Hash,
+    marker::PhantomData,
+};
+
+use super::Fingerprint;
+
+/// The error returned when merging two sketches whose parameters differ.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IncompatibleSketches;
+
+impl Display for IncompatibleSketches {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sketches with different parameters can't be merged")
+    }
+}
+
+impl std::error::Error for IncompatibleSketches {}
+
+/// The `i`th of a family of hashes derived from one fingerprint, reduced to `0..modulus`.
+fn nth_hash(fingerprint: Fingerprint, i: u64, modulus: usize) -> usize {
+    let (h1, h2) = fingerprint.halves();
+    // An odd step visits every slot of a power-of-two-sized table before repeating.
+    let combined = h1.wrapping_add(i.wrapping_mul(h2 | 1));
+    (combined % modulus as u64) as usize
+}
+
+/// A set that can answer "definitely not present" or "probably present".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BloomFilter<T> {
+    bits: Vec<u64>,
+    bit_count: usize,
+    hash_count: u32,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: Hash> BloomFilter<T> {
+    /// A filter with `bit_count` bits that sets `hash_count` bits per value.
+    pub fn new(bit_count: usize, hash_count: u32) -> Self {
+        assert!(bit_count > 0, "a Bloom filter needs at least one bit");
+        assert!(hash_count > 0, "a Bloom filter needs at least one hash");
+        Self {
+            bits: vec![0; bit_count.div_ceil(64)],
+            bit_count,
+            hash_count,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The smallest filter whose false positive rate is at most `false_positive_rate` once it holds
+    /// `expected_items` values.
+    pub fn with_false_positive_rate(expected_items: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "the false positive rate must be strictly between 0 and 1"
+        );
+        let n = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-n * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+        let hash_count = (bit_count / n * ln2).round().max(1.0);
+        Self::new(bit_count as usize, hash_count as u32)
+    }
+
+    pub fn insert(&mut self, value: &T) {
+        self.insert_fingerprint(Fingerprint::of(value));
+    }
+
+    /// `false` means `value` was never inserted. `true` means it probably was.
+    pub fn contains(&self, value: &T) -> bool {
+        self.contains_fingerprint(Fingerprint::of(value))
+    }
+}
+
+impl<T> BloomFilter<T> {
+    pub fn insert_fingerprint(&mut self, fingerprint: Fingerprint) {
+        for i in 0..self.hash_count {
+            let bit = nth_hash(fingerprint, i.into(), self.bit_count);
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains_fingerprint(&self, fingerprint: Fingerprint) -> bool {
+        (0..self.hash_count).all(|i| {
+            let bit = nth_hash(fingerprint, i.into(), self.bit_count);
+            self.bits[bit / 64] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    /// Makes `self` the filter of everything inserted into either filter.
+    pub fn merge(&mut self, other: &Self) -> Result<(), IncompatibleSketches> {
+        if (self.bit_count, self.hash_count) != (other.bit_count, other.hash_count) {
+            return Err(IncompatibleSketches);
+        }
+        for (word, other_word) in self.bits.iter_mut().zip(&other.bits) {
+            *word |= other_word;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    pub fn bit_count(&self) -> usize {
+        self.bit_count
+    }
+
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// The probability that [`contains`](Self::contains) returns `true` for a value that was never
+    /// inserted, estimated from how many bits are set.
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        let set_bits: u32 = self.bits.iter().map(|word| word.count_ones()).sum();
+        (set_bits as f64 / self.bit_count as f64).powi(self.hash_count as i32)
+    }
+}
+
+/// Estimates the number of distinct values it has seen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HyperLogLog {
+    /// For each bucket, the largest number of leading zeros (plus one) seen in a hash that fell
+    /// into it.
+    registers: Vec<u8>,
+    precision: u8,
+}
+
+impl HyperLogLog {
+    pub const MIN_PRECISION: u8 = 4;
+    pub const MAX_PRECISION: u8 = 18;
+
+    /// An estimator with `2^precision` one-byte registers. Each increment of `precision` doubles
+    /// the memory and divides the error by `sqrt(2)`.
+    pub fn new(precision: u8) -> Self {
+        assert!(
+            (Self::MIN_PRECISION..=Self::MAX_PRECISION).contains(&precision),
+            "HyperLogLog precision must be between {} and {}",
+            Self::MIN_PRECISION,
+            Self::MAX_PRECISION
+        );
+        Self {
+            registers: vec![0; 1 << precision],
+            precision,
+        }
+    }
+
+    pub fn insert<T: Hash>(&mut self, value: &T) {
+        self.insert_fingerprint(Fingerprint::of(value));
+    }
+
+    pub fn insert_fingerprint(&mut self, fingerprint: Fingerprint) {
+        let hash = fingerprint.as_u64();
+        let bucket = (hash >> (64 - self.precision)) as usize;
+        // The remaining bits, with a sentinel so an all-zero remainder has a bounded rank.
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        let register = &mut self.registers[bucket];
+        *register = (*register).max(rank);
+    }
+
+    /// The estimated number of distinct values inserted.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| (-f64::from(rank)).exp2())
+            .sum();
+        let raw = alpha * m * m / sum;
+
+        // The raw estimate is biased upwards for small cardinalities, where linear counting of the
+        // empty registers is more accurate.
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw <= 2.5 * m && empty > 0 {
+            m * (m / empty as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /// The relative standard error of [`estimate`](Self::estimate).
+    pub fn standard_error(&self) -> f64 {
+        1.04 / (self.registers.len() as f64).sqrt()
+    }
+
+    /// Makes `self` the estimator of everything inserted into either estimator.
+    pub fn merge(&mut self, other: &Self) -> Result<(), IncompatibleSketches> {
+        if self.precision != other.precision {
+            return Err(IncompatibleSketches);
+        }
+        for (register, &other_register) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(other_register);
+        }
+        Ok(())
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+}
+
+/// Estimates how many times each value has been counted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountMinSketch<T> {
+    /// `depth` rows of `width` counters, row-major.
+    counters: Vec<u64>,
+    width: usize,
+    depth: usize,
+    total: u64,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: Hash> CountMinSketch<T> {
+    /// A sketch with `depth` rows of `width` counters.
+    pub fn new(width: usize, depth: usize) -> Self {
+        assert!(
+            width > 0 && depth > 0,
+            "a Count-Min sketch needs at least one counter"
+        );
+        Self {
+            counters: vec![0; width * depth],
+            width,
+            depth,
+            total: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The smallest sketch whose estimates exceed the true count by at most `epsilon` times
+    /// [`total`](Self::total) with probability at least `1 - delta`.
+    pub fn with_error(epsilon: f64, delta: f64) -> Self {
+        assert!(epsilon > 0.0, "epsilon must be positive");
+        assert!(
+            delta > 0.0 && delta < 1.0,
+            "delta must be strictly between 0 and 1"
+        );
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil().max(1.0) as usize;
+        Self::new(width, depth)
+    }
+
+    pub fn add(&mut self, value: &T, count: u64) {
+        self.add_fingerprint(Fingerprint::of(value), count);
+    }
+
+    pub fn increment(&mut self, value: &T) {
+        self.add(value, 1);
+    }
+
+    /// An upper bound on how many times `value` was counted, and usually the exact count.
+    pub fn estimate(&self, value: &T) -> u64 {
+        self.estimate_fingerprint(Fingerprint::of(value))
+    }
+}
+
+impl<T> CountMinSketch<T> {
+    pub fn add_fingerprint(&mut self, fingerprint: Fingerprint, count: u64) {
+        for row in 0..self.depth {
+            let column = nth_hash(fingerprint, row as u64, self.width);
+            let counter = &mut self.counters[row * self.width + column];
+            *counter = counter.saturating_add(count);
+        }
+        self.total = self.total.saturating_add(count);
+    }
+
+    pub fn estimate_fingerprint(&self, fingerprint: Fingerprint) -> u64 {
+        (0..self.depth)
+            .map(|row| {
+                let column = nth_hash(fingerprint, row as u64, self.width);
+                self.counters[row * self.width + column]
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Makes `self` the sketch of everything counted by either sketch.
+    pub fn merge(&mut self, other: &Self) -> Result<(), IncompatibleSketches> {
+        if (self.width, self.depth) != (other.width, other.depth) {
+            return Err(IncompatibleSketches);
+        }
+        for (counter, &other_counter) in self.counters.iter_mut().zip(&other.counters) {
+            *counter = counter.saturating_add(other_counter);
+        }
+        self.total = self.total.saturating_add(other.total);
+        Ok(())
+    }
+
+    /// The sum of all counts added.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::type_erasure::type_erased_api::Index;
+
+    #[test]
+    fn bloom_filter_has_no_false_negatives_and_few_false_positives() {
+        let target = 0.01;
+        let mut filter = BloomFilter::with_false_positive_rate(10_000, target);
+        for i in 0..10_000u64 {
+            filter.insert(&i);
+        }
+        assert!((0..10_000u64).all(|i| filter.contains(&i)));
+
+        let false_positives = (10_000..110_000u64).filter(|i| filter.contains(i)).count();
+        let rate = false_positives as f64 / 100_000.0;
+        assert!(rate < 2.0 * target, "false positive rate {rate}");
+        assert!((filter.estimated_false_positive_rate() - target).abs() < target);
+    }
+
+    #[test]
+    fn merged_bloom_filters_contain_both_sides() {
+        let mut evens = BloomFilter::new(4096, 4);
+        let mut odds = BloomFilter::new(4096, 4);
+        for i in 0..200u32 {
+            if i % 2 == 0 { &mut evens } else { &mut odds }.insert(&i);
+        }
+        evens.merge(&odds).unwrap();
+        assert!((0..200u32).all(|i| evens.contains(&i)));
+
+        assert_eq!(
+            evens.merge(&BloomFilter::new(4096, 3)),
+            Err(IncompatibleSketches)
+        );
+    }
+
+    #[test]
+    fn hyperloglog_is_within_its_error_bound() {
+        for (precision, distinct) in [(10, 100usize), (12, 100_000), (14, 250_000)] {
+            let mut hll = HyperLogLog::new(precision);
+            for i in 0..distinct {
+                // Duplicates don't count.
+                hll.insert(&i);
+                hll.insert(&i);
+            }
+            let error = (hll.estimate() - distinct as f64).abs() / distinct as f64;
+            // Three standard errors.
+            assert!(
+                error < 3.0 * hll.standard_error(),
+                "precision {precision}, {distinct} distinct values: relative error {error}"
+            );
+        }
+        assert_eq!(HyperLogLog::new(8).estimate(), 0.0);
+    }
+
+    #[test]
+    fn merged_hyperloglog_equals_hyperloglog_of_the_union() {
+        let mut left = HyperLogLog::new(12);
+        let mut right = HyperLogLog::new(12);
+        let mut both = HyperLogLog::new(12);
+        for i in 0..50_000u32 {
+            left.insert(&i);
+            both.insert(&i);
+        }
+        for i in 25_000..75_000u32 {
+            right.insert(&i);
+            both.insert(&i);
+        }
+        left.merge(&right).unwrap();
+        assert_eq!(left, both);
+        assert_eq!(left.merge(&HyperLogLog::new(11)), Err(IncompatibleSketches));
+    }
+
+    #[test]
+    fn count_min_sketch_never_underestimates() {
+        let epsilon = 0.001;
+        let mut sketch = CountMinSketch::with_error(epsilon, 0.01);
+        // Value `i` occurs `i % 100 + 1` times.
+        for i in 0..10_000u32 {
+            sketch.add(&i, (i % 100 + 1).into());
+        }
+        let bound = (epsilon * sketch.total() as f64) as u64;
+        let mut over_bound = 0;
+        for i in 0..10_000u32 {
+            let truth = u64::from(i % 100 + 1);
+            let estimate = sketch.estimate(&i);
+            assert!(estimate >= truth);
+            if estimate > truth + bound {
+                over_bound += 1;
+            }
+        }
+        // The bound may fail for at most a `delta` fraction of values.
+        assert!(
+            over_bound <= 100,
+            "{over_bound} estimates exceeded the bound"
+        );
+        // A value that was never counted only picks up noise from the others.
+        assert!(sketch.estimate(&u32::MAX) <= bound);
+    }
+
+    #[test]
+    fn merged_count_min_sketches_add_up() {
+        let mut left = CountMinSketch::new(256, 4);
+        let mut right = CountMinSketch::new(256, 4);
+        left.add(&"a", 3);
+        right.add(&"a", 4);
+        right.increment(&"b");
+        left.merge(&right).unwrap();
+        assert_eq!(left.estimate(&"a"), 7);
+        assert_eq!(left.estimate(&"b"), 1);
+        assert_eq!(left.total(), 8);
+        assert_eq!(
+            left.merge(&CountMinSketch::new(128, 4)),
+            Err(IncompatibleSketches)
+        );
+    }
+
+    #[test]
+    fn sketches_accept_index_hashes() {
+        // The index's hashes are the values' fingerprints, so a sketch fed hashes agrees with one fed
+        // values.
+        let index = Index::<String>::new();
+        let mut by_value = HyperLogLog::new(10);
+        let mut by_hash = HyperLogLog::new(10);
+        let mut counts = CountMinSketch::<String>::new(64, 3);
+        for word in ["a", "b", "a", "c"].map(String::from) {
+            by_value.insert(&word);
+            by_hash.insert_fingerprint(index.hash_value(&word));
+            counts.add_fingerprint(index.hash_value(&word), 1);
+        }
+        assert_eq!(by_value, by_hash);
+        assert_eq!(counts.estimate(&"a".to_string()), 2);
+    }
+}
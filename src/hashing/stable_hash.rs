@@ -0,0 +1,391 @@
+/*!
+
+# Platform-Stable Hashing
+
+`std::hash::Hash` is designed for in-memory hash tables, not for hashes you write to disk or send
+over the wire. The byte stream a `Hash` impl feeds to the `Hasher` is an implementation detail:
+
+- `usize` and `isize` are hashed at their native width, so 32-bit and 64-bit targets disagree.
+- `Hasher::write_u32` and friends default to native-endian bytes, so big- and little-endian targets
+  disagree.
+- The standard library is free to change how it hashes its own types (and has, e.g. for `str`'s
+  length prefix vs. `0xff` terminator) between releases.
+
+None of this matters for a `HashMap` that lives and dies in a single process, but it means
+[`one_shot_128`](super::one_shot_128) is the wrong tool for a key you want to persist. The
+[`StableHash`] trait is a parallel universe to `Hash` with a _documented_, canonical encoding. The
+digest of a value is the 128-bit XXH3 (seed `0`) of the concatenation of the bytes below, which is
+what [`stable_one_shot_128`] computes.
+
+| Type                                          | Encoding                                                          |
+|-----------------------------------------------|-------------------------------------------------------------------|
+| `u8`..`u128`, `i8`..`i128`                    | fixed-width little-endian two's complement                        |
+| `usize`, `isize`                              | as `u64`, `i64`                                                   |
+| `bool`                                        | one byte, `0` or `1`                                              |
+| `char`                                        | as `u32` (the Unicode scalar value)                               |
+| `f32`, `f64`                                  | the IEEE 754 bit pattern as `u32`, `u64` (so `0.0 != -0.0`)       |
+| `str`, `String`                               | length in bytes as `u64`, then the UTF-8 bytes                    |
+| `[T]`, `Vec<T>`, `[T; N]`, `VecDeque<T>`      | length as `u64`, then each element                                |
+| `BTreeSet<T>`, `BTreeMap<K, V>`               | length as `u64`, then each element (or key, value) in order       |
+| `()`, `PhantomData<T>`                        | nothing                                                           |
+| tuples                                        | each element in order, no prefix                                  |
+| `&T`, `&mut T`, `Box<T>`, `Rc<T>`, `Arc<T>`   | as `T`                                                            |
+| `Option<T>`                                   | as the enum `{ None, Some(T) }`                                   |
+| `Result<T, E>`                                | as the enum `{ Ok(T), Err(E) }`                                   |
+| `#[derive(StableHash)]` struct                | each field in declaration order, no prefix                        |
+| `#[derive(StableHash)]` enum                  | variant index in declaration order as `u32`, then its fields      |
+
+A few consequences worth spelling out:
+
+- Reordering the fields of a struct or the variants of an enum changes its stable hash. Renaming
+  them does not. This is the same contract as most binary serialization formats.
+- Sequences carry a length prefix so that `(vec![1], vec![2, 3])` and `(vec![1, 2], vec![3])`
+  don't collide. Tuples and structs don't need one because their shape is fixed by the type.
+- There is deliberately no impl for `HashMap` and `HashSet`, whose iteration order is not
+  deterministic.
+
+The golden-value tests at the bottom of this file pin both the encoding and the digests. If one of
+them fails, you've changed the on-disk format, and that had better be on purpose.
+
+*/
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    rc::Rc,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{hash::Hasher, marker::PhantomData};
+
+/// Derives [`StableHash`] with the encoding described in the [module docs](self).
+pub use rust_patterns_macros::StableHash;
+
+use super::{Hasher128, Xxh3Hasher128};
+
+/// A `Hash`-like trait whose byte stream is identical on every platform and every Rust release.
+/// See the [module docs](self) for the encoding.
+pub trait StableHash {
+    /// Feeds the canonical encoding of `self` to `state`.
+    fn stable_hash<H: Hasher>(&self, state: &mut H);
+}
+
+/// The 128-bit stable hash of `value`, suitable for persisting.
+pub fn stable_one_shot_128<T: StableHash + ?Sized>(value: &T) -> u128 {
+    let mut h = Xxh3Hasher128::default();
+    value.stable_hash(&mut h);
+    h.finish_u128()
+}
+
+/// Writes a sequence length. Lengths are always `u64` regardless of the width of `usize`.
+fn write_len<H: Hasher>(len: usize, state: &mut H) {
+    state.write(&(len as u64).to_le_bytes());
+}
+
+macro_rules! impl_stable_hash_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl StableHash for $ty {
+                fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                    state.write(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_stable_hash_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl StableHash for usize {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as u64).stable_hash(state);
+    }
+}
+
+impl StableHash for isize {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as i64).stable_hash(state);
+    }
+}
+
+impl StableHash for bool {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as u8).stable_hash(state);
+    }
+}
+
+impl StableHash for char {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as u32).stable_hash(state);
+    }
+}
+
+impl StableHash for f32 {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().stable_hash(state);
+    }
+}
+
+impl StableHash for f64 {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().stable_hash(state);
+    }
+}
+
+impl StableHash for str {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        write_len(self.len(), state);
+        state.write(self.as_bytes());
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().stable_hash(state);
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        write_len(self.len(), state);
+        for item in self {
+            item.stable_hash(state);
+        }
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().stable_hash(state);
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().stable_hash(state);
+    }
+}
+
+impl<T: StableHash> StableHash for VecDeque<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        write_len(self.len(), state);
+        for item in self {
+            item.stable_hash(state);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for BTreeSet<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        write_len(self.len(), state);
+        for item in self {
+            item.stable_hash(state);
+        }
+    }
+}
+
+impl<K: StableHash, V: StableHash> StableHash for BTreeMap<K, V> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        write_len(self.len(), state);
+        for (key, value) in self {
+            key.stable_hash(state);
+            value.stable_hash(state);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            None => 0u32.stable_hash(state),
+            Some(value) => {
+                1u32.stable_hash(state);
+                value.stable_hash(state);
+            }
+        }
+    }
+}
+
+impl<T: StableHash, E: StableHash> StableHash for Result<T, E> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Ok(value) => {
+                0u32.stable_hash(state);
+                value.stable_hash(state);
+            }
+            Err(error) => {
+                1u32.stable_hash(state);
+                error.stable_hash(state);
+            }
+        }
+    }
+}
+
+impl StableHash for () {
+    fn stable_hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl<T: ?Sized> StableHash for PhantomData<T> {
+    fn stable_hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+macro_rules! impl_stable_hash_for_pointer {
+    ($($ptr:ty),*) => {
+        $(
+            impl<T: StableHash + ?Sized> StableHash for $ptr {
+                fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                    (**self).stable_hash(state);
+                }
+            }
+        )*
+    };
+}
+
+impl_stable_hash_for_pointer!(&T, &mut T, Box<T>, Rc<T>, Arc<T>);
+
+impl<T: StableHash + ToOwned + ?Sized> StableHash for Cow<'_, T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).stable_hash(state);
+    }
+}
+
+macro_rules! impl_stable_hash_for_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: StableHash),+> StableHash for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                let ($($name,)+) = self;
+                $($name.stable_hash(state);)+
+            }
+        }
+    };
+}
+
+impl_stable_hash_for_tuple!(T0);
+impl_stable_hash_for_tuple!(T0 T1);
+impl_stable_hash_for_tuple!(T0 T1 T2);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4 T5);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4 T5 T6);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4 T5 T6 T7);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+impl_stable_hash_for_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the raw byte stream so we can pin the encoding itself, independent of XXH3.
+    #[derive(Default)]
+    struct ByteRecorder(Vec<u8>);
+
+    impl Hasher for ByteRecorder {
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+
+        fn finish(&self) -> u64 {
+            unreachable!("ByteRecorder only records bytes")
+        }
+    }
+
+    fn encode<T: StableHash + ?Sized>(value: &T) -> Vec<u8> {
+        let mut recorder = ByteRecorder::default();
+        value.stable_hash(&mut recorder);
+        recorder.0
+    }
+
+    #[derive(StableHash)]
+    struct Person {
+        age: u8,
+        name: String,
+        county: Option<u16>,
+    }
+
+    #[derive(StableHash)]
+    enum Shape {
+        Empty,
+        Circle(u32),
+        Rect { w: u16, h: u16 },
+    }
+
+    #[derive(StableHash)]
+    struct Wrapper<T>(T);
+
+    #[test]
+    fn integers_are_fixed_width_little_endian() {
+        assert_eq!(encode(&0x0102_0304u32), [4, 3, 2, 1]);
+        assert_eq!(encode(&-2i16), [0xfe, 0xff]);
+        assert_eq!(encode(&1usize), [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(encode(&-1isize), [0xff; 8]);
+        assert_eq!(encode(&true), [1]);
+        assert_eq!(encode(&'A'), [0x41, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sequences_are_length_prefixed() {
+        assert_eq!(encode("hi"), [2, 0, 0, 0, 0, 0, 0, 0, b'h', b'i']);
+        assert_eq!(encode(&"hi".to_string()), encode("hi"));
+        assert_eq!(encode(&vec![1u8, 2]), [2, 0, 0, 0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(encode(&[1u8, 2]), encode(&vec![1u8, 2]));
+        assert_ne!(
+            stable_one_shot_128(&(vec![1u8], vec![2u8, 3])),
+            stable_one_shot_128(&(vec![1u8, 2], vec![3u8]))
+        );
+    }
+
+    #[test]
+    fn derived_encoding() {
+        let person = Person {
+            age: 7,
+            name: "Al".to_string(),
+            county: Some(3),
+        };
+        assert_eq!(
+            encode(&person),
+            [
+                7, // age
+                2, 0, 0, 0, 0, 0, 0, 0, b'A', b'l', // name
+                1, 0, 0, 0, 3, 0, // Some(3)
+            ]
+        );
+
+        assert_eq!(encode(&Shape::Empty), [0, 0, 0, 0]);
+        assert_eq!(encode(&Shape::Circle(5)), [1, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(
+            encode(&Shape::Rect { w: 1, h: 2 }),
+            [2, 0, 0, 0, 1, 0, 2, 0]
+        );
+        assert_eq!(encode(&Wrapper(5u32)), encode(&5u32));
+        assert_eq!(encode(&Some(5u32)), encode(&Shape::Circle(5)));
+    }
+
+    #[test]
+    fn golden_digests() {
+        assert_eq!(
+            stable_one_shot_128(&42u64),
+            0x9bab_3e2f_55e1_fe86_61c2_164e_8b89_5a45
+        );
+        assert_eq!(
+            stable_one_shot_128("hello"),
+            0xe22e_b3a2_1af2_e1e3_1912_3040_3fea_e166
+        );
+        assert_eq!(
+            stable_one_shot_128(&Person {
+                age: 7,
+                name: "Al".to_string(),
+                county: None,
+            }),
+            0xe569_f876_e181_fc72_07ed_79a6_dd3b_43aa
+        );
+        assert_eq!(
+            stable_one_shot_128(&Shape::Rect { w: 1, h: 2 }),
+            0xdadb_4011_068d_4052_8751_6927_0c39_0c60
+        );
+    }
+}
@@ -0,0 +1,142 @@
+/*!
+
+# Streaming Digests
+
+[`Xxh3Hasher128`] implements [`std::io::Write`], so anything that can write to a sink can write
+to the hasher instead: `io::copy`, `serde_json::to_writer`, a `BufWriter` you were going to use
+anyway. [`hash_reader_128`] and [`hash_file_128`] build on that to fingerprint arbitrarily large
+inputs, like scenario files or content-addressed cache entries, in a fixed-size buffer without
+ever holding the whole input in memory.
+
+Note that these digests are of the _raw bytes_. They equal `XxHash3_128::oneshot(bytes)` but not
+`one_shot_128(&bytes)`, because the `Hash` impl of a slice prefixes its length.
+
+*/
+
+use std::{
+    fs::File,
+    hash::Hasher,
+    io::{self, ErrorKind, Read},
+    path::Path,
+};
+
+use super::{Hasher128, Xxh3Hasher128};
+
+/// The buffer size used by [`hash_reader_128`] and [`hash_file_128`]. XXH3 is fast enough that
+/// the per-call overhead of small reads shows up, so this is bigger than `io::copy`'s 8 KiB.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+impl io::Write for Xxh3Hasher128 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Hasher::write(self, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The 128-bit digest of everything `reader` produces, read in chunks of [`DEFAULT_BUFFER_SIZE`].
+pub fn hash_reader_128(reader: impl Read) -> io::Result<u128> {
+    hash_reader_128_with_buffer(reader, &mut vec![0; DEFAULT_BUFFER_SIZE])
+}
+
+/// Like [`hash_reader_128`], but reads into a caller-supplied buffer, which bounds the memory
+/// used. `buffer` must not be empty.
+pub fn hash_reader_128_with_buffer(mut reader: impl Read, buffer: &mut [u8]) -> io::Result<u128> {
+    assert!(!buffer.is_empty(), "the read buffer must not be empty");
+    let mut hasher = Xxh3Hasher128::default();
+    loop {
+        match reader.read(buffer) {
+            Ok(0) => return Ok(hasher.finish_u128()),
+            Ok(n) => Hasher::write(&mut hasher, &buffer[..n]),
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// The 128-bit digest of the contents of the file at `path`.
+pub fn hash_file_128(path: impl AsRef<Path>) -> io::Result<u128> {
+    hash_reader_128(File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use twox_hash::XxHash3_128;
+
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    /// Hands out at most `chunk` bytes per read and is interrupted before every other read.
+    struct Stuttering<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        interrupt: bool,
+    }
+
+    impl Read for Stuttering<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn writer_matches_oneshot() {
+        let data = sample(10_000);
+        let mut hasher = Xxh3Hasher128::default();
+        for chunk in data.chunks(333) {
+            hasher.write_all(chunk).unwrap();
+        }
+        assert_eq!(hasher.finish_u128(), XxHash3_128::oneshot(&data));
+    }
+
+    #[test]
+    fn reader_digest_does_not_depend_on_chunking() {
+        let data = sample(3 * DEFAULT_BUFFER_SIZE + 17);
+        let expected = XxHash3_128::oneshot(&data);
+        assert_eq!(hash_reader_128(data.as_slice()).unwrap(), expected);
+
+        let stuttering = Stuttering {
+            data: &data,
+            chunk: 1000,
+            interrupt: false,
+        };
+        assert_eq!(
+            hash_reader_128_with_buffer(stuttering, &mut [0; 7]).unwrap(),
+            expected
+        );
+        assert_eq!(
+            hash_reader_128(io::empty()).unwrap(),
+            XxHash3_128::oneshot(&[])
+        );
+    }
+
+    #[test]
+    fn file_digest() {
+        let data = sample(100_000);
+        let path = std::env::temp_dir().join(format!(
+            "rust_patterns_file_digest_{}.bin",
+            std::process::id()
+        ));
+        std::fs::write(&path, &data).unwrap();
+        let digest = hash_file_128(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(digest.unwrap(), XxHash3_128::oneshot(&data));
+
+        assert!(hash_file_128(&path).is_err());
+    }
+}
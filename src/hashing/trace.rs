@@ -0,0 +1,426 @@
+/*!
+
+# Hash Traces
+
+When two values that should be equal have different fingerprints, the digests themselves say
+nothing about why. The difference is always in what the values' `Hash` impls _write_ to the hasher,
+so a [`TracingHasher`] records every `write_*` call, with its method and argument, while computing
+the same digest as [`Xxh3Hasher128`]. [`trace_hash`] traces a value, and [`HashTrace::diff`] lines
+two traces up side by side:
+
+```text
+  left                               right
+  write_u64(7)                       write_u64(7)
+! write_usize(140732912464512)       write_usize(140732912464704)
+  write([41 6c] "Al")                write([41 6c] "Al")
+  write_u8(255)                      write_u8(255)
+```
+
+The `!` row is the culprit: a `usize` that differs between two "equal" values is almost always a
+pointer or a length that shouldn't be hashed. Rows only one side has are marked `-` (left only) and
+`+` (right only).
+
+*/
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+};
+
+use super::{Hasher128, Xxh3Hasher128};
+
+/// One call to a [`Hasher`] method, with its argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashWrite {
+    Bytes(Vec<u8>),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    Usize(usize),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    Isize(isize),
+}
+
+impl HashWrite {
+    /// The name of the `Hasher` method that was called.
+    pub fn method_name(&self) -> &'static str {
+        match self {
+            Self::Bytes(_) => "write",
+            Self::U8(_) => "write_u8",
+            Self::U16(_) => "write_u16",
+            Self::U32(_) => "write_u32",
+            Self::U64(_) => "write_u64",
+            Self::U128(_) => "write_u128",
+            Self::Usize(_) => "write_usize",
+            Self::I8(_) => "write_i8",
+            Self::I16(_) => "write_i16",
+            Self::I32(_) => "write_i32",
+            Self::I64(_) => "write_i64",
+            Self::I128(_) => "write_i128",
+            Self::Isize(_) => "write_isize",
+        }
+    }
+
+    /// The bytes the default implementation of the method feeds to the hasher: the argument itself
+    /// for `write`, and the native-endian bytes of the integer otherwise.
+    pub fn bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Bytes(bytes) => bytes.clone(),
+            Self::U8(value) => value.to_ne_bytes().to_vec(),
+            Self::U16(value) => value.to_ne_bytes().to_vec(),
+            Self::U32(value) => value.to_ne_bytes().to_vec(),
+            Self::U64(value) => value.to_ne_bytes().to_vec(),
+            Self::U128(value) => value.to_ne_bytes().to_vec(),
+            Self::Usize(value) => value.to_ne_bytes().to_vec(),
+            Self::I8(value) => value.to_ne_bytes().to_vec(),
+            Self::I16(value) => value.to_ne_bytes().to_vec(),
+            Self::I32(value) => value.to_ne_bytes().to_vec(),
+            Self::I64(value) => value.to_ne_bytes().to_vec(),
+            Self::I128(value) => value.to_ne_bytes().to_vec(),
+            Self::Isize(value) => value.to_ne_bytes().to_vec(),
+        }
+    }
+}
+
+/// `write_u64(42)`, or `write([68 69] "hi")` for byte slices, with the text shown when the bytes
+/// are printable UTF-8.
+impl Display for HashWrite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}(", self.method_name())?;
+        match self {
+            Self::Bytes(bytes) => {
+                write!(f, "[")?;
+                for (i, byte) in bytes.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { " " };
+                    write!(f, "{separator}{byte:02x}")?;
+                }
+                write!(f, "]")?;
+                if let Ok(text) = core::str::from_utf8(bytes)
+                    && !text.is_empty()
+                    && !text.chars().any(char::is_control)
+                {
+                    write!(f, " {text:?}")?;
+                }
+            }
+            Self::U8(value) => write!(f, "{value}")?,
+            Self::U16(value) => write!(f, "{value}")?,
+            Self::U32(value) => write!(f, "{value}")?,
+            Self::U64(value) => write!(f, "{value}")?,
+            Self::U128(value) => write!(f, "{value}")?,
+            Self::Usize(value) => write!(f, "{value}")?,
+            Self::I8(value) => write!(f, "{value}")?,
+            Self::I16(value) => write!(f, "{value}")?,
+            Self::I32(value) => write!(f, "{value}")?,
+            Self::I64(value) => write!(f, "{value}")?,
+            Self::I128(value) => write!(f, "{value}")?,
+            Self::Isize(value) => write!(f, "{value}")?,
+        }
+        write!(f, ")")
+    }
+}
+
+/// The sequence of writes a value's `Hash` impl made.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HashTrace(pub Vec<HashWrite>);
+
+/// Traces the hashing of `value`.
+pub fn trace_hash<T: Hash + ?Sized>(value: &T) -> HashTrace {
+    let mut hasher = TracingHasher::default();
+    value.hash(&mut hasher);
+    hasher.into_trace()
+}
+
+/// A [`Hasher`] that records every write and forwards it to an [`Xxh3Hasher128`], so its digests
+/// are the same as the untraced ones.
+#[derive(Default)]
+pub struct TracingHasher {
+    trace: Vec<HashWrite>,
+    inner: Xxh3Hasher128,
+}
+
+impl TracingHasher {
+    /// Traces into a seeded hasher, e.g. to trace what a seeded index sees.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            trace: Vec::new(),
+            inner: Xxh3Hasher128::with_seed(seed),
+        }
+    }
+
+    pub fn trace(&self) -> &[HashWrite] {
+        &self.trace
+    }
+
+    pub fn into_trace(self) -> HashTrace {
+        HashTrace(self.trace)
+    }
+}
+
+/// Each method records its call, then calls the same method on the inner hasher.
+macro_rules! traced_writes {
+    ($($method:ident($type:ty) => $variant:ident,)*) => {
+        $(
+            fn $method(&mut self, value: $type) {
+                self.trace.push(HashWrite::$variant(value));
+                self.inner.$method(value);
+            }
+        )*
+    };
+}
+
+impl Hasher for TracingHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.trace.push(HashWrite::Bytes(bytes.to_vec()));
+        self.inner.write(bytes);
+    }
+
+    traced_writes! {
+        write_u8(u8) => U8,
+        write_u16(u16) => U16,
+        write_u32(u32) => U32,
+        write_u64(u64) => U64,
+        write_u128(u128) => U128,
+        write_usize(usize) => Usize,
+        write_i8(i8) => I8,
+        write_i16(i16) => I16,
+        write_i32(i32) => I32,
+        write_i64(i64) => I64,
+        write_i128(i128) => I128,
+        write_isize(isize) => Isize,
+    }
+
+    fn finish(&self) -> u64 {
+        self.inner.finish()
+    }
+}
+
+impl Hasher128 for TracingHasher {
+    fn finish_u128(&self) -> u128 {
+        self.inner.finish_u128()
+    }
+}
+
+/// One row of a [`TraceDiff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffRow {
+    /// Both traces made this write.
+    Same(HashWrite),
+    /// The traces made different writes at this point.
+    Changed(HashWrite, HashWrite),
+    /// Only the left trace made this write.
+    LeftOnly(HashWrite),
+    /// Only the right trace made this write.
+    RightOnly(HashWrite),
+}
+
+/// Two traces aligned side by side. See the [module docs](self) for the `Display` format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceDiff {
+    pub rows: Vec<DiffRow>,
+}
+
+impl TraceDiff {
+    pub fn is_identical(&self) -> bool {
+        self.first_difference().is_none()
+    }
+
+    /// The index into [`rows`](Self::rows) of the first row that isn't [`DiffRow::Same`].
+    pub fn first_difference(&self) -> Option<usize> {
+        self.rows
+            .iter()
+            .position(|row| !matches!(row, DiffRow::Same(_)))
+    }
+}
+
+impl HashTrace {
+    /// Aligns the two traces on their longest common subsequence of writes, so a single extra or
+    /// missing write shows up as one `+` or `-` row rather than shifting everything after it.
+    pub fn diff(&self, other: &HashTrace) -> TraceDiff {
+        let (left, right) = (&self.0, &other.0);
+
+        // `lcs[i][j]` is the length of the longest common subsequence of `left[i..]` and
+        // `right[j..]`.
+        let mut lcs = vec![vec![0u32; right.len() + 1]; left.len() + 1];
+        for i in (0..left.len()).rev() {
+            for j in (0..right.len()).rev() {
+                lcs[i][j] = if left[i] == right[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut rows = Vec::new();
+        let (mut lefts, mut rights) = (Vec::new(), Vec::new());
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() || j < right.len() {
+            if i < left.len() && j < right.len() && left[i] == right[j] {
+                flush_unmatched(&mut rows, &mut lefts, &mut rights);
+                rows.push(DiffRow::Same(left[i].clone()));
+                i += 1;
+                j += 1;
+            } else if j == right.len() || (i < left.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                lefts.push(left[i].clone());
+                i += 1;
+            } else {
+                rights.push(right[j].clone());
+                j += 1;
+            }
+        }
+        flush_unmatched(&mut rows, &mut lefts, &mut rights);
+        TraceDiff { rows }
+    }
+}
+
+/// Pairs up a run of left-only writes with the run of right-only writes next to it as changed
+/// rows, which is how a human reads "this write differs".
+fn flush_unmatched(
+    rows: &mut Vec<DiffRow>,
+    lefts: &mut Vec<HashWrite>,
+    rights: &mut Vec<HashWrite>,
+) {
+    let mut lefts = lefts.drain(..);
+    let mut rights = rights.drain(..);
+    loop {
+        match (lefts.next(), rights.next()) {
+            (Some(left), Some(right)) => rows.push(DiffRow::Changed(left, right)),
+            (Some(left), None) => rows.push(DiffRow::LeftOnly(left)),
+            (None, Some(right)) => rows.push(DiffRow::RightOnly(right)),
+            (None, None) => break,
+        }
+    }
+}
+
+impl Display for TraceDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let cells: Vec<(char, String, String)> = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                DiffRow::Same(write) => (' ', write.to_string(), write.to_string()),
+                DiffRow::Changed(left, right) => ('!', left.to_string(), right.to_string()),
+                DiffRow::LeftOnly(left) => ('-', left.to_string(), String::new()),
+                DiffRow::RightOnly(right) => ('+', String::new(), right.to_string()),
+            })
+            .collect();
+        let width = cells
+            .iter()
+            .map(|(_, left, _)| left.len())
+            .max()
+            .unwrap_or(0)
+            .max("left".len());
+
+        write!(f, "  {:width$}   right", "left")?;
+        for (marker, left, right) in cells {
+            write!(f, "\n{marker} {left:width$}   {right}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::one_shot_128;
+
+    #[test]
+    fn tracing_does_not_change_the_digest() {
+        let value = ("hello", 42u64, [1u8, 2, 3], -7i32);
+        let mut hasher = TracingHasher::default();
+        value.hash(&mut hasher);
+        assert_eq!(hasher.finish_u128(), one_shot_128(&value));
+        assert_eq!(hasher.trace()[1], HashWrite::U8(0xff));
+    }
+
+    #[test]
+    fn records_each_write() {
+        let trace = trace_hash(&(7u32, "hi"));
+        assert_eq!(
+            trace.0,
+            vec![
+                HashWrite::U32(7),
+                HashWrite::Bytes(b"hi".to_vec()),
+                HashWrite::U8(0xff)
+            ]
+        );
+        assert_eq!(trace.0[1].to_string(), r#"write([68 69] "hi")"#);
+        assert_eq!(trace.0[2].to_string(), "write_u8(255)");
+        assert_eq!(trace.0[0].bytes(), 7u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn diff_points_at_a_hashed_pointer() {
+        /// Hashes the address of its name, so equal values hash differently.
+        #[derive(PartialEq)]
+        struct Person {
+            id: u64,
+            name: Box<str>,
+        }
+
+        impl Hash for Person {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.id.hash(state);
+                (self.name.as_ptr() as usize).hash(state);
+            }
+        }
+
+        let a = Person {
+            id: 7,
+            name: "Al".into(),
+        };
+        let b = Person {
+            id: 7,
+            name: "Al".into(),
+        };
+        assert!(a == b);
+        assert_ne!(one_shot_128(&a), one_shot_128(&b));
+
+        let diff = trace_hash(&a).diff(&trace_hash(&b));
+        assert_eq!(diff.first_difference(), Some(1));
+        assert!(matches!(
+            &diff.rows[1],
+            DiffRow::Changed(HashWrite::Usize(_), HashWrite::Usize(_))
+        ));
+        let text = diff.to_string();
+        assert!(text.lines().nth(2).unwrap().starts_with("! write_usize("));
+    }
+
+    #[test]
+    fn diff_aligns_around_extra_writes() {
+        let left = HashTrace(vec![HashWrite::U8(1), HashWrite::U8(2), HashWrite::U8(3)]);
+        let right = HashTrace(vec![
+            HashWrite::U8(1),
+            HashWrite::U64(9),
+            HashWrite::U8(2),
+            HashWrite::U8(3),
+        ]);
+        let diff = left.diff(&right);
+        assert_eq!(
+            diff.rows,
+            vec![
+                DiffRow::Same(HashWrite::U8(1)),
+                DiffRow::RightOnly(HashWrite::U64(9)),
+                DiffRow::Same(HashWrite::U8(2)),
+                DiffRow::Same(HashWrite::U8(3)),
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "  left          right\n  write_u8(1)   write_u8(1)\n+               write_u64(9)\n  \
+             write_u8(2)   write_u8(2)\n  write_u8(3)   write_u8(3)"
+        );
+        assert!(left.diff(&left).is_identical());
+    }
+}
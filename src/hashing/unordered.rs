@@ -0,0 +1,256 @@
+/*!
+
+# Order-Independent Hashing
+
+A set or map is a perfectly good value to fingerprint, for deduplication, change detection, or as
+a memoization key. But `std`'s `HashSet` and `HashMap` don't implement `Hash`, and hashing their
+elements in iteration order gives a different answer for equal sets whenever the iteration order
+differs, which it does between instances, between processes, and after every rehash.
+
+The fix is to hash each element on its own and combine the element hashes with a commutative
+operation. The obvious commutative operation, XOR, is a trap: `x ^ x == 0`, so an element that
+appears twice in a multiset cancels itself out, and in general XOR-ing hashes leaks structure that
+makes collisions easy to construct. We use wrapping addition of the 128-bit element hashes instead
+(see [`Fingerprint::combine_unordered`]) and then fold in the element count with an
+order-sensitive [`Fingerprint::combine`], so that the final digest is a well-mixed hash rather
+than a raw sum.
+
+[`UnorderedHashSet`] and [`UnorderedHashMap`] are thin wrappers around
+[`FastHashSet`](super::FastHashSet) and [`FastHashMap`](super::FastHashMap) that implement `Hash`
+this way. They deref to the wrapped collection, so they can be used like one.
+
+*/
+
+use core::{
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+};
+
+use super::{FastHashMap, FastHashSet, Fingerprint};
+
+/// A 128-bit hash of a collection of items that doesn't depend on the order they're produced in.
+/// Duplicates count: `[a, a]` and `[a]` hash differently.
+pub fn unordered_hash_128<I>(items: I) -> u128
+where
+    I: IntoIterator<Item: Hash>,
+{
+    let (sum, count) = items
+        .into_iter()
+        .fold((Fingerprint::ZERO, 0u64), |(sum, count), item| {
+            (sum.combine_unordered(Fingerprint::of(&item)), count + 1)
+        });
+    Fingerprint::new(count as u128).combine(sum).as_u128()
+}
+
+/// A [`FastHashSet`] that implements `Hash` with [`unordered_hash_128`].
+#[derive(Clone, Debug)]
+pub struct UnorderedHashSet<T>(FastHashSet<T>);
+
+/// A [`FastHashMap`] that implements `Hash` with [`unordered_hash_128`] over its `(key, value)`
+/// pairs.
+#[derive(Clone, Debug)]
+pub struct UnorderedHashMap<K, V>(FastHashMap<K, V>);
+
+impl<T> UnorderedHashSet<T> {
+    pub fn new() -> Self {
+        Self(FastHashSet::default())
+    }
+
+    pub fn into_inner(self) -> FastHashSet<T> {
+        self.0
+    }
+}
+
+impl<K, V> UnorderedHashMap<K, V> {
+    pub fn new() -> Self {
+        Self(FastHashMap::default())
+    }
+
+    pub fn into_inner(self) -> FastHashMap<K, V> {
+        self.0
+    }
+}
+
+impl<T: Hash> Hash for UnorderedHashSet<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u128(unordered_hash_128(&self.0));
+    }
+}
+
+impl<K: Hash, V: Hash> Hash for UnorderedHashMap<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u128(unordered_hash_128(&self.0));
+    }
+}
+
+impl<T: Hash + Eq> PartialEq for UnorderedHashSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: Hash + Eq> Eq for UnorderedHashSet<T> {}
+
+impl<K: Hash + Eq, V: PartialEq> PartialEq for UnorderedHashMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K: Hash + Eq, V: Eq> Eq for UnorderedHashMap<K, V> {}
+
+impl<T> Default for UnorderedHashSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Default for UnorderedHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Deref for UnorderedHashSet<T> {
+    type Target = FastHashSet<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for UnorderedHashSet<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K, V> Deref for UnorderedHashMap<K, V> {
+    type Target = FastHashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V> DerefMut for UnorderedHashMap<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> From<FastHashSet<T>> for UnorderedHashSet<T> {
+    fn from(set: FastHashSet<T>) -> Self {
+        Self(set)
+    }
+}
+
+impl<K, V> From<FastHashMap<K, V>> for UnorderedHashMap<K, V> {
+    fn from(map: FastHashMap<K, V>) -> Self {
+        Self(map)
+    }
+}
+
+impl<T: Hash + Eq> FromIterator<T> for UnorderedHashSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(FastHashSet::from_iter(iter))
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for UnorderedHashMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(FastHashMap::from_iter(iter))
+    }
+}
+
+impl<T> IntoIterator for UnorderedHashSet<T> {
+    type Item = T;
+    type IntoIter = <FastHashSet<T> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a UnorderedHashSet<T> {
+    type Item = &'a T;
+    type IntoIter = <&'a FastHashSet<T> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<K, V> IntoIterator for UnorderedHashMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = <FastHashMap<K, V> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a UnorderedHashMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = <&'a FastHashMap<K, V> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::one_shot_128;
+
+    #[test]
+    fn order_does_not_matter() {
+        assert_eq!(
+            unordered_hash_128([1, 2, 3]),
+            unordered_hash_128(vec![3, 1, 2])
+        );
+        assert_ne!(unordered_hash_128([1, 2, 3]), unordered_hash_128([1, 2, 4]));
+    }
+
+    #[test]
+    fn duplicates_and_empty_collections_are_distinguished() {
+        let empty = unordered_hash_128(Vec::<u32>::new());
+        let one = unordered_hash_128([7]);
+        let two = unordered_hash_128([7, 7]);
+        assert_ne!(empty, one);
+        assert_ne!(one, two);
+        assert_ne!(empty, two);
+        // XOR would map both of these to the hash of `[5]`.
+        assert_ne!(unordered_hash_128([5, 9, 9]), unordered_hash_128([5]));
+    }
+
+    #[test]
+    fn equal_sets_hash_equally_regardless_of_insertion_order() {
+        let forwards: UnorderedHashSet<u64> = (0..1000).collect();
+        let backwards: UnorderedHashSet<u64> = (0..1000).rev().collect();
+        assert_eq!(forwards, backwards);
+        assert_eq!(one_shot_128(&forwards), one_shot_128(&backwards));
+
+        let mut fewer = forwards.clone();
+        fewer.remove(&500);
+        assert_ne!(one_shot_128(&forwards), one_shot_128(&fewer));
+    }
+
+    #[test]
+    fn maps_hash_keys_and_values() {
+        let a: UnorderedHashMap<&str, u32> = [("x", 1), ("y", 2)].into_iter().collect();
+        let b: UnorderedHashMap<&str, u32> = [("y", 2), ("x", 1)].into_iter().collect();
+        let swapped: UnorderedHashMap<&str, u32> = [("x", 2), ("y", 1)].into_iter().collect();
+        assert_eq!(one_shot_128(&a), one_shot_128(&b));
+        assert_ne!(one_shot_128(&a), one_shot_128(&swapped));
+    }
+
+    #[test]
+    fn sets_can_be_set_elements() {
+        let mut sets_of_sets = FastHashSet::default();
+        sets_of_sets.insert(UnorderedHashSet::from_iter(["a", "b"]));
+        assert!(!sets_of_sets.insert(UnorderedHashSet::from_iter(["b", "a"])));
+        assert!(sets_of_sets.insert(UnorderedHashSet::from_iter(["a"])));
+    }
+}
@@ -0,0 +1,509 @@
+/*!
+
+# Interning
+
+Interning stores one canonical copy of each distinct value and hands out a small `Copy` handle, a
+_symbol_, in its place. Comparing or hashing two symbols is as cheap as comparing two integers, no
+matter how big the values are, which makes symbols ideal keys for an
+[`Index`](crate::type_erasure::type_erased_api::Index).
+
+This is one of the use cases the [`hashing`](crate::hashing) module docs say should be hidden
+behind a module of your own instead of reaching for `string_cache` or `ustr` all over the
+codebase. This is that module. The rest of the crate only ever sees [`Symbol`], [`Interner`], and
+the free functions here.
+
+## Design
+
+A [`Symbol<T>`] _is_ the [`Fingerprint`] of the value (as computed by
+[`one_shot_128`](crate::hashing::one_shot_128)) with a phantom type attached, so:
+
+- the same value gets the same symbol in every interner, every thread, and every run;
+- a symbol can be created from a value without touching an interner at all ([`Symbol::of`]),
+  which is handy for lookups;
+- the interner's storage is keyed by something that is already a hash, so it uses a
+  [`FingerprintMap`](crate::hashing::FingerprintMap) that doesn't hash it again.
+
+The price is 16 bytes per symbol instead of the 4 of an index-based interner, and a symbol only
+resolves in an interner that has actually seen its value.
+
+There are three flavors, from least to most shared:
+
+- [`Interner<T>`]: an ordinary owned value. Mutation requires `&mut`. Resolves to `&T`.
+- The thread-local interners, one per `T` per thread: [`intern_local`], [`resolve_local`],
+  [`with_local_interner`].
+- The global interners, one per `T` per process: [`global_interner`] returns a `&'static`
+  [`SyncInterner<T>`], which takes `&self` everywhere and resolves to `Arc<T>`.
+
+## Backends
+
+The storage of [`Interner<T>`] is a cargo-feature-selected type alias, just like the maps in
+[`hashing::collections`](crate::hashing::collections). By default it's a `FingerprintMap`. With the
+`interner-indexmap` feature it's an `IndexMap`, which makes [`Interner::iter`] yield values in the
+order they were first interned.
+
+*/
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    cmp::Ordering,
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    sync::{
+        Arc, LazyLock, RwLock,
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+    },
+};
+
+use crate::hashing::{FastHashMap, Fingerprint};
+
+/// The map from fingerprint to interned value backing an [`Interner`].
+#[cfg(not(feature = "interner-indexmap"))]
+type Storage<T> = crate::hashing::FingerprintMap<Arc<T>>;
+/// The map from fingerprint to interned value backing an [`Interner`].
+#[cfg(feature = "interner-indexmap")]
+type Storage<T> = indexmap::IndexMap<Fingerprint, Arc<T>, crate::hashing::BuildFingerprintHasher>;
+
+/// A handle to an interned `T`.
+pub struct Symbol<T> {
+    fingerprint: Fingerprint,
+    // `fn() -> T` makes `Symbol<T>` `Send + Sync` and covariant regardless of `T`.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Hash> Symbol<T> {
+    /// The symbol `value` has (or would have) in any interner.
+    pub fn of(value: &T) -> Self {
+        Self::from_fingerprint(Fingerprint::of(value))
+    }
+}
+
+impl<T> Symbol<T> {
+    pub const fn from_fingerprint(fingerprint: Fingerprint) -> Self {
+        Self {
+            fingerprint,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn fingerprint(self) -> Fingerprint {
+        self.fingerprint
+    }
+}
+
+// Manual impls, because derives would put bounds on `T`.
+
+impl<T> Copy for Symbol<T> {}
+
+impl<T> Clone for Symbol<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for Symbol<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint
+    }
+}
+
+impl<T> Eq for Symbol<T> {}
+
+impl<T> PartialOrd for Symbol<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by fingerprint, which is arbitrary but stable.
+impl<T> Ord for Symbol<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.fingerprint.cmp(&other.fingerprint)
+    }
+}
+
+impl<T> Hash for Symbol<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fingerprint.hash(state);
+    }
+}
+
+impl<T> Debug for Symbol<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Symbol({})", self.fingerprint)
+    }
+}
+
+/// A snapshot of an interner's counters.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InternerStats {
+    /// The number of distinct values interned.
+    pub symbols: usize,
+    /// The number of calls to the `intern*` methods.
+    pub intern_calls: u64,
+    /// The number of those calls that found the value already interned.
+    pub hits: u64,
+}
+
+impl InternerStats {
+    /// The fraction of `intern*` calls that didn't have to store a new value.
+    pub fn hit_rate(&self) -> f64 {
+        if self.intern_calls == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.intern_calls as f64
+        }
+    }
+}
+
+/// Stores one copy of each distinct `T` and identifies it by a [`Symbol<T>`].
+pub struct Interner<T> {
+    values: Storage<T>,
+    // Atomic so that `SyncInterner` can count hits while holding only a read lock.
+    intern_calls: AtomicU64,
+    hits: AtomicU64,
+}
+
+impl<T> Default for Interner<T> {
+    fn default() -> Self {
+        Self {
+            values: Storage::default(),
+            intern_calls: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<T: Hash + Eq> Interner<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Interns `value`, returning its symbol. If an equal value is already interned, `value` is
+    /// dropped.
+    pub fn intern(&mut self, value: T) -> Symbol<T> {
+        let symbol = Symbol::of(&value);
+        if self.lookup_counted(symbol, &value).is_none() {
+            self.values.insert(symbol.fingerprint, Arc::new(value));
+        }
+        symbol
+    }
+
+    /// Like [`intern`](Self::intern), but only clones `value` if it isn't already interned.
+    pub fn intern_ref(&mut self, value: &T) -> Symbol<T>
+    where
+        T: Clone,
+    {
+        let symbol = Symbol::of(value);
+        if self.lookup_counted(symbol, value).is_none() {
+            self.values
+                .insert(symbol.fingerprint, Arc::new(value.clone()));
+        }
+        symbol
+    }
+
+    /// Interns a value that is already shared. No copy is made either way.
+    pub fn intern_arc(&mut self, value: Arc<T>) -> Symbol<T> {
+        let symbol = Symbol::of(&*value);
+        if self.lookup_counted(symbol, &value).is_none() {
+            self.values.insert(symbol.fingerprint, value);
+        }
+        symbol
+    }
+
+    /// The symbol for `value` if it has been interned, without interning it.
+    pub fn get(&self, value: &T) -> Option<Symbol<T>> {
+        let symbol = Symbol::of(value);
+        self.values
+            .contains_key(&symbol.fingerprint)
+            .then_some(symbol)
+    }
+
+    /// Counts an `intern*` call and returns the stored value if there is one.
+    fn lookup_counted(&self, symbol: Symbol<T>, value: &T) -> Option<&Arc<T>> {
+        self.intern_calls.fetch_add(1, AtomicOrdering::Relaxed);
+        let stored = self.values.get(&symbol.fingerprint)?;
+        debug_assert!(
+            **stored == *value,
+            "hash collision: two unequal values have the fingerprint {}",
+            symbol.fingerprint
+        );
+        self.hits.fetch_add(1, AtomicOrdering::Relaxed);
+        Some(stored)
+    }
+}
+
+impl<T> Interner<T> {
+    pub fn resolve(&self, symbol: Symbol<T>) -> Option<&T> {
+        self.values.get(&symbol.fingerprint).map(|value| &**value)
+    }
+
+    pub fn resolve_arc(&self, symbol: Symbol<T>) -> Option<Arc<T>> {
+        self.values.get(&symbol.fingerprint).cloned()
+    }
+
+    pub fn contains(&self, symbol: Symbol<T>) -> bool {
+        self.values.contains_key(&symbol.fingerprint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Iterates over every interned value and its symbol. See the [module docs](self) for the
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol<T>, &T)> {
+        self.values
+            .iter()
+            .map(|(fingerprint, value)| (Symbol::from_fingerprint(*fingerprint), &**value))
+    }
+
+    pub fn stats(&self) -> InternerStats {
+        InternerStats {
+            symbols: self.values.len(),
+            intern_calls: self.intern_calls.load(AtomicOrdering::Relaxed),
+            hits: self.hits.load(AtomicOrdering::Relaxed),
+        }
+    }
+}
+
+/// A thread-safe [`Interner`]. Lookups of values that are already interned, which is the common
+/// case, only take a read lock.
+pub struct SyncInterner<T> {
+    inner: RwLock<Interner<T>>,
+}
+
+impl<T> Default for SyncInterner<T> {
+    fn default() -> Self {
+        Self {
+            inner: RwLock::new(Interner::default()),
+        }
+    }
+}
+
+impl<T: Hash + Eq> SyncInterner<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&self, value: T) -> Symbol<T> {
+        let symbol = Symbol::of(&value);
+        if self.read().lookup_counted(symbol, &value).is_some() {
+            return symbol;
+        }
+        // Another thread may have interned `value` between the two locks. `intern` copes with
+        // that, at the cost of counting this call twice.
+        self.write().intern(value)
+    }
+
+    pub fn intern_ref(&self, value: &T) -> Symbol<T>
+    where
+        T: Clone,
+    {
+        let symbol = Symbol::of(value);
+        if self.read().lookup_counted(symbol, value).is_some() {
+            return symbol;
+        }
+        self.write().intern_ref(value)
+    }
+
+    pub fn get(&self, value: &T) -> Option<Symbol<T>> {
+        self.read().get(value)
+    }
+}
+
+impl<T> SyncInterner<T> {
+    pub fn resolve(&self, symbol: Symbol<T>) -> Option<Arc<T>> {
+        self.read().resolve_arc(symbol)
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub fn stats(&self) -> InternerStats {
+        self.read().stats()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Interner<T>> {
+        // A panic while holding the lock can't leave the map half-updated, so poisoning is moot.
+        self.inner.read().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Interner<T>> {
+        self.inner
+            .write()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+thread_local! {
+    /// One `Interner<T>` per `T`, created on first use.
+    static LOCAL_INTERNERS: RefCell<FastHashMap<TypeId, Box<dyn Any>>> =
+        RefCell::new(FastHashMap::default());
+}
+
+/// Calls `f` with this thread's interner for `T`. Calling any of the thread-local functions for
+/// the same `T` from inside `f` panics, because the interner is already borrowed.
+pub fn with_local_interner<T: Hash + Eq + 'static, R>(f: impl FnOnce(&mut Interner<T>) -> R) -> R {
+    LOCAL_INTERNERS.with_borrow_mut(|interners| {
+        let interner = interners
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Interner::<T>::new()))
+            .downcast_mut::<Interner<T>>()
+            .expect("interners are keyed by the `TypeId` of their value type");
+        f(interner)
+    })
+}
+
+/// Interns `value` in this thread's interner for `T`.
+pub fn intern_local<T: Hash + Eq + 'static>(value: T) -> Symbol<T> {
+    with_local_interner(|interner| interner.intern(value))
+}
+
+/// Resolves `symbol` in this thread's interner for `T`. Returns an `Arc` because a reference
+/// can't escape the thread-local borrow.
+pub fn resolve_local<T: Hash + Eq + 'static>(symbol: Symbol<T>) -> Option<Arc<T>> {
+    with_local_interner(|interner| interner.resolve_arc(symbol))
+}
+
+/// The process-wide interners, one per `T`, created on first use and never freed.
+static GLOBAL_INTERNERS: LazyLock<RwLock<FastHashMap<TypeId, &'static (dyn Any + Send + Sync)>>> =
+    LazyLock::new(|| RwLock::new(FastHashMap::default()));
+
+/// The process-wide interner for `T`.
+pub fn global_interner<T: Hash + Eq + Send + Sync + 'static>() -> &'static SyncInterner<T> {
+    let type_id = TypeId::of::<T>();
+    let existing = GLOBAL_INTERNERS
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .get(&type_id)
+        .copied();
+    let interner = existing.unwrap_or_else(|| {
+        *GLOBAL_INTERNERS
+            .write()
+            .unwrap_or_else(|error| error.into_inner())
+            .entry(type_id)
+            .or_insert_with(|| Box::leak(Box::new(SyncInterner::<T>::new())))
+    });
+    interner
+        .downcast_ref::<SyncInterner<T>>()
+        .expect("interners are keyed by the `TypeId` of their value type")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::type_erasure::type_erased_api::Index;
+
+    #[test]
+    fn interning_deduplicates() {
+        let mut interner = Interner::new();
+        let a = interner.intern("alpha".to_string());
+        let b = interner.intern_ref(&"beta".to_string());
+        let a_again = interner.intern("alpha".to_string());
+
+        assert_eq!(a, a_again);
+        assert_ne!(a, b);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.resolve(a).map(String::as_str), Some("alpha"));
+        assert_eq!(
+            interner.resolve_arc(b).as_deref(),
+            Some(&"beta".to_string())
+        );
+        assert_eq!(interner.get(&"alpha".to_string()), Some(a));
+        assert_eq!(interner.get(&"gamma".to_string()), None);
+        assert_eq!(
+            interner.stats(),
+            InternerStats {
+                symbols: 2,
+                intern_calls: 3,
+                hits: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn symbols_are_the_same_in_every_interner() {
+        let mut first = Interner::new();
+        let mut second = Interner::new();
+        second.intern(1u32);
+        let symbol = first.intern(2u32);
+        assert_eq!(symbol, second.intern(2u32));
+        assert_eq!(symbol, Symbol::of(&2u32));
+
+        // But a symbol only resolves where its value was interned.
+        let mut third = Interner::<u32>::new();
+        assert_eq!(third.resolve(symbol), None);
+        third.intern(2);
+        assert_eq!(third.resolve(symbol), Some(&2));
+    }
+
+    #[test]
+    fn thread_local_interners_are_per_thread() {
+        let symbol = intern_local("local".to_string());
+        assert_eq!(resolve_local(symbol).as_deref(), Some(&"local".to_string()));
+
+        let other_thread = thread::spawn(move || resolve_local(symbol)).join().unwrap();
+        assert_eq!(other_thread, None);
+
+        // Distinct value types get distinct interners.
+        intern_local(5u8);
+        assert_eq!(with_local_interner::<u8, _>(|interner| interner.len()), 1);
+    }
+
+    #[test]
+    fn global_interner_is_shared_across_threads() {
+        // A private type so no other test touches this interner.
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        struct Name(String);
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                thread::spawn(move || {
+                    let interner = global_interner::<Name>();
+                    (
+                        interner.intern(Name("shared".to_string())),
+                        interner.intern_ref(&Name(format!("thread {i}"))),
+                    )
+                })
+            })
+            .collect();
+        let symbols: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let interner = global_interner::<Name>();
+        assert!(symbols.iter().all(|(shared, _)| *shared == symbols[0].0));
+        assert_eq!(interner.len(), 9);
+        assert_eq!(
+            interner.resolve(symbols[3].1).as_deref(),
+            Some(&Name("thread 3".to_string()))
+        );
+        let stats = interner.stats();
+        assert_eq!(stats.symbols, 9);
+        assert!(stats.hits >= 7);
+        assert!(std::ptr::eq(interner, global_interner::<Name>()));
+    }
+
+    #[test]
+    fn symbols_make_cheap_index_keys() {
+        let mut interner = Interner::new();
+        let mut index = Index::<Symbol<String>>::new();
+        let county = interner.intern("Los Angeles".to_string());
+        index.insert_entity(&county, 1).unwrap();
+        index
+            .insert_entity(&interner.intern("Los Angeles".to_string()), 2)
+            .unwrap();
+        assert_eq!(index.get(&county).map(|set| set.len()), Some(2));
+    }
+}
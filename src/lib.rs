@@ -1,4 +1,5 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 /*!
 
@@ -35,12 +36,23 @@ the macro generates is obviously only correct if the macro itself is correct.
 
 */
 
+extern crate alloc;
+
+// Lets the `::rust_patterns::...` paths emitted by our derive macros resolve inside this crate too.
+extern crate self as rust_patterns;
+
 pub mod data_structures;
 pub mod hashing;
+#[cfg(feature = "std")]
+pub mod interning;
+#[cfg(feature = "plugins")]
 pub mod plugins;
+#[cfg(feature = "std")]
 pub mod shared_implementation;
 pub mod type_erasure;
 
 // Re-exported for use in exported macros
+#[cfg(feature = "plugins")]
 pub use ctor;
+#[cfg(feature = "plugins")]
 pub use paste;
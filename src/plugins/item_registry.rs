@@ -136,7 +136,6 @@ My advice is to choose (2) almost always. Superficial implementation features ar
 use std::{
     any::{Any, TypeId},
     cell::{OnceCell, RefCell},
-    collections::HashSet,
     sync::{
         LazyLock, Mutex,
         atomic::{AtomicUsize, Ordering},
@@ -145,6 +144,8 @@ use std::{
 
 use polonius_the_crab::{polonius, polonius_return};
 
+use crate::hashing::{FastHashSet, Fingerprint, xxh3_128};
+
 /// A trait for items that can be registered (`DataPlugin`, `PersonProperty`)
 pub trait RegisteredItem: Any + Default {
     /// Convenient for debugging.
@@ -152,6 +153,19 @@ pub trait RegisteredItem: Any + Default {
     where
         Self: Sized;
 
+    /// A hash of the item's fully qualified type name. Unlike `TypeId` or [`index`](Self::index), it's the same in
+    /// every build and every run, so it can be persisted or sent to another process to identify the item type.
+    ///
+    /// [`registered_item_impl!`](crate::registered_item_impl) computes it at compile time from the item's module
+    /// path. The default hashes [`std::any::type_name`] instead, which names the type the same way in practice but
+    /// isn't guaranteed to across compiler versions.
+    fn stable_id() -> Fingerprint
+    where
+        Self: Sized,
+    {
+        Fingerprint::new(xxh3_128(std::any::type_name::<Self>().as_bytes()))
+    }
+
     /// The index of the item in the [`RegisteredItem`] list, typically just a vector that lives in the parent.
     fn index() -> usize
     where
@@ -162,7 +176,7 @@ pub trait RegisteredItem: Any + Default {
     where
         Self: Sized,
     {
-        Box::new(Default::default())
+        Box::default()
     }
 }
 
@@ -194,8 +208,8 @@ pub static NEXT_ITEM_INDEX: Mutex<usize> = Mutex::new(0);
 /// global data store, then the client code can just use the `add_to_registry` method
 /// to add items to the store and wouldn't need to worry about the implementation.
 #[allow(unused)]
-pub static REGISTERED_ITEMS: LazyLock<Mutex<RefCell<HashSet<TypeId>>>> =
-    LazyLock::new(|| Mutex::new(RefCell::new(HashSet::default())));
+pub static REGISTERED_ITEMS: LazyLock<Mutex<RefCell<FastHashSet<TypeId>>>> =
+    LazyLock::new(|| Mutex::new(RefCell::new(FastHashSet::default())));
 
 /// Adds a new item to the registry. The job of this method is to create whatever "singleton"
 /// data/metadata is associated with the [`RegisteredItem`] if it doesn't already exist. The "registry"
@@ -282,6 +296,12 @@ pub struct RegisteredItems {
     items: Vec<OnceCell<Box<dyn Any>>>,
 }
 
+impl Default for RegisteredItems {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RegisteredItems {
     /// Creates a new [`RegisteredItems`] instance, allocating the exact number
     /// of slots as there are types that implement [`RegisteredItem`]s.
@@ -350,6 +370,10 @@ impl RegisteredItems {
 /// This macro ensures correct implementation of the `RegisteredItem` trait. The tricky bit is the implementation of
 /// `RegisteredItem::index`, which requires synchronization in multithreaded runtimes. This is an instance of
 /// _correctness via macro_.
+///
+/// The item's [`stable_id`](RegisteredItem::stable_id) is computed from `module_path!()` and the item's name, which
+/// `module_path!()` can't tell apart for two items with the same name declared inside different functions of one
+/// module. Such items get the same ID, so declare registered items at module level.
 #[macro_export]
 macro_rules! registered_item_impl {
     ($item_name:ident) => {
@@ -361,6 +385,17 @@ macro_rules! registered_item_impl {
                 stringify!($item_name)
             }
 
+            fn stable_id() -> $crate::hashing::Fingerprint
+            where
+                Self: Sized,
+            {
+                // Computed at compile time and stored in read-only memory.
+                static STABLE_ID: $crate::hashing::Fingerprint = $crate::const_fingerprint!(
+                    concat!(module_path!(), "::", stringify!($item_name))
+                );
+                STABLE_ID
+            }
+
             fn index() -> usize {
                 // This static must be initialized with a compile-time constant expression.
                 // We use `usize::MAX` as a sentinel to mean "uninitialized". This
@@ -450,6 +485,7 @@ mod tests {
     // because at worst `RegisteredItems` will just allocate addition slots for
     // nonexistent items, which will never be requested with a `get()` call.
     #[test]
+    #[allow(clippy::identity_op)]
     fn test_initialize_item_index_concurrent() {
         // Test 1: Try to initialize a single index from multiple threads simultaneously.
         let initial_registered_items_count = get_registered_item_count();
@@ -766,6 +802,41 @@ mod tests {
         assert_eq!(TestItem3::name(), "TestItem3");
     }
 
+    #[test]
+    fn test_registered_item_stable_id() {
+        // The stable ID is the XXH3 digest of the fully qualified type name.
+        assert_eq!(
+            TestItem1::stable_id().as_u128(),
+            twox_hash::XxHash3_128::oneshot(
+                b"rust_patterns::plugins::item_registry::tests::TestItem1"
+            )
+        );
+        assert_ne!(TestItem1::stable_id(), TestItem2::stable_id());
+        assert_ne!(TestItem2::stable_id(), TestItem3::stable_id());
+    }
+
+    /// Implemented by hand rather than with `registered_item_impl!`.
+    #[derive(Default)]
+    struct ManualItem;
+
+    impl RegisteredItem for ManualItem {
+        fn name() -> &'static str {
+            "ManualItem"
+        }
+
+        fn index() -> usize {
+            unreachable!("never registered")
+        }
+    }
+
+    #[test]
+    fn test_default_stable_id_matches_the_macro() {
+        assert_eq!(
+            ManualItem::stable_id(),
+            crate::const_fingerprint!(concat!(module_path!(), "::ManualItem"))
+        );
+    }
+
     #[test]
     fn test_registered_item_new_boxed() {
         let boxed1 = TestItem1::new_boxed();
@@ -6,8 +6,8 @@ Writing a plugin system in Rust usually involves two main components:
 
 1. A mechanism for providing a generic interface between the main program and the plugin.
 2. A mechanism for plugin discovery, which you might call "initialization"
-  or "registration", but which is more about knowing which plugins exist,
-  possibly across crate boundaries, without explicit central declaration.
+   or "registration", but which is more about knowing which plugins exist,
+   possibly across crate boundaries, without explicit central declaration.
 
 The first is well-trodden ground in Rust. You typically use a trait to define
 the generic interface. (See [crate::type_erased_api].)
@@ -0,0 +1,882 @@
+/*!
+
+# Entity Sets
+
+Each value in an [`Index`](super::type_erased_api::Index) has a set of the entities that have it.
+A `HashSet<EntityId>` is a fine default, but it costs a dozen or more bytes per entity, and
+intersecting two of them means probing one for every element of the other. When IDs are dense, as
+they are when entities are numbered as they're created, other representations are much smaller
+and faster to combine. The index is generic over its set type, any [`EntitySet`]:
+
+- `FastHashSet<EntityId>`, the default. Good for sparse IDs and sets that change a lot.
+- [`SortedVecSet`], a sorted `Vec`. Eight bytes per entity, merged in linear time. Good for
+  small sets and sets that are built once and rarely change, since inserts shift the tail.
+- [`DenseBitSet`], one bit for every ID from zero to the largest in the set. Tiny and very fast when
+  IDs are dense, and wasteful when they aren't: a single entity with ID one million costs 125 KB.
+  It assumes IDs are handed out from zero, and refuses any above
+  [`DenseBitSet::MAX_ENTITY_ID`] rather than allocate up to the ID.
+- [`RoaringSet`], a roaring-style bitmap. IDs are split into chunks of 2<sup>16</sup>, and each
+  chunk is stored as a sorted array of its low 16 bits when it has at most 4096 entities, and as a
+  bitset when it has more. It stays compact for both sparse and dense IDs, which makes it the safe
+  choice when you don't know.
+
+The trait is object safe, so the type-erased API hands out `&dyn EntitySet` whatever the
+representation. The set operations, [`union`](EntitySet::union),
+[`intersection`](EntitySet::intersection) and [`difference`](EntitySet::difference), need two sets
+of the same type and are only available on the concrete types.
+
+*/
+
+use std::{any::Any, fmt::Debug, mem::size_of};
+
+use super::type_erased_api::EntityId;
+use crate::hashing::FastHashSet;
+
+/// A set of entities, as stored for each value of an index.
+pub trait EntitySet: Debug + Any {
+    /// An empty set.
+    fn new() -> Self
+    where
+        Self: Sized;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, entity_id: EntityId) -> bool;
+
+    /// Returns whether the entity was newly inserted. An ID above [`max_entity_id`](Self::max_entity_id) isn't
+    /// inserted, and returns `false`.
+    fn insert(&mut self, entity_id: EntityId) -> bool;
+
+    /// The largest ID the set can hold. An [`Index`](super::type_erased_api::Index) checks it before inserting, and
+    /// reports a larger ID as [`IndexError::EntityOutOfRange`](super::type_erased_api::IndexError::EntityOutOfRange).
+    fn max_entity_id() -> EntityId
+    where
+        Self: Sized,
+    {
+        EntityId::MAX
+    }
+
+    /// Returns whether the entity was in the set.
+    fn remove(&mut self, entity_id: EntityId) -> bool;
+
+    /// Iterates over the entities in the set. Every set here but `FastHashSet` yields them in increasing order.
+    fn iter_ids(&self) -> Box<dyn Iterator<Item = EntityId> + '_>;
+
+    /// The heap memory the set owns, in bytes, including capacity it hasn't used yet. The set value itself isn't
+    /// counted: it lives wherever its owner keeps it, e.g. inline in an index's table.
+    fn heap_bytes(&self) -> usize;
+
+    /// The entities in either set.
+    fn union(&self, other: &Self) -> Self
+    where
+        Self: Sized;
+
+    /// The entities in both sets.
+    fn intersection(&self, other: &Self) -> Self
+    where
+        Self: Sized;
+
+    /// The entities in `self` but not in `other`.
+    fn difference(&self, other: &Self) -> Self
+    where
+        Self: Sized;
+
+    /// Copies the set into a `FastHashSet`, whatever its representation.
+    fn to_hash_set(&self) -> FastHashSet<EntityId> {
+        self.iter_ids().collect()
+    }
+}
+
+impl EntitySet for FastHashSet<EntityId> {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn len(&self) -> usize {
+        hashbrown::HashSet::len(self)
+    }
+
+    fn contains(&self, entity_id: EntityId) -> bool {
+        hashbrown::HashSet::contains(self, &entity_id)
+    }
+
+    fn insert(&mut self, entity_id: EntityId) -> bool {
+        hashbrown::HashSet::insert(self, entity_id)
+    }
+
+    fn remove(&mut self, entity_id: EntityId) -> bool {
+        hashbrown::HashSet::remove(self, &entity_id)
+    }
+
+    fn iter_ids(&self) -> Box<dyn Iterator<Item = EntityId> + '_> {
+        Box::new(self.iter().copied())
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.allocation_size()
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        hashbrown::HashSet::union(self, other).copied().collect()
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        hashbrown::HashSet::intersection(self, other)
+            .copied()
+            .collect()
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        hashbrown::HashSet::difference(self, other)
+            .copied()
+            .collect()
+    }
+
+    fn to_hash_set(&self) -> FastHashSet<EntityId> {
+        self.clone()
+    }
+}
+
+/// The elements of either sorted slice, sorted.
+fn sorted_union<T: Ord + Copy>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut union = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let next = a[i].min(b[j]);
+        i += usize::from(a[i] == next);
+        j += usize::from(b[j] == next);
+        union.push(next);
+    }
+    union.extend_from_slice(&a[i..]);
+    union.extend_from_slice(&b[j..]);
+    union
+}
+
+/// The elements of both sorted slices, sorted.
+fn sorted_intersection<T: Ord + Copy>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut intersection = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                intersection.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    intersection
+}
+
+/// The elements of sorted `a` that aren't in sorted `b`, sorted.
+fn sorted_difference<T: Ord + Copy>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut j = 0;
+    for &element in a {
+        while j < b.len() && b[j] < element {
+            j += 1;
+        }
+        if b.get(j) != Some(&element) {
+            difference.push(element);
+        }
+    }
+    difference
+}
+
+/// The positions of the set bits of `word`, lowest first.
+fn set_bits(mut word: u64) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || {
+        (word != 0).then(|| {
+            let bit = word.trailing_zeros();
+            word &= word - 1;
+            bit
+        })
+    })
+}
+
+/// A set stored as a sorted `Vec` without duplicates.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SortedVecSet(Vec<EntityId>);
+
+impl SortedVecSet {
+    /// The entities in increasing order.
+    pub fn as_slice(&self) -> &[EntityId] {
+        &self.0
+    }
+}
+
+impl FromIterator<EntityId> for SortedVecSet {
+    fn from_iter<I: IntoIterator<Item = EntityId>>(entity_ids: I) -> Self {
+        let mut entity_ids: Vec<_> = entity_ids.into_iter().collect();
+        entity_ids.sort_unstable();
+        entity_ids.dedup();
+        Self(entity_ids)
+    }
+}
+
+impl EntitySet for SortedVecSet {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn contains(&self, entity_id: EntityId) -> bool {
+        self.0.binary_search(&entity_id).is_ok()
+    }
+
+    fn insert(&mut self, entity_id: EntityId) -> bool {
+        match self.0.binary_search(&entity_id) {
+            Ok(_) => false,
+            Err(position) => {
+                self.0.insert(position, entity_id);
+                true
+            }
+        }
+    }
+
+    fn remove(&mut self, entity_id: EntityId) -> bool {
+        match self.0.binary_search(&entity_id) {
+            Ok(position) => {
+                self.0.remove(position);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn iter_ids(&self) -> Box<dyn Iterator<Item = EntityId> + '_> {
+        Box::new(self.0.iter().copied())
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.0.capacity() * size_of::<EntityId>()
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self(sorted_union(&self.0, &other.0))
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        Self(sorted_intersection(&self.0, &other.0))
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        Self(sorted_difference(&self.0, &other.0))
+    }
+}
+
+/// A set stored as one bit for each ID from zero up to the largest ID in the set.
+///
+/// Its size follows the largest ID, not the number of entities, so it's only a good choice when IDs are dense. IDs
+/// above [`MAX_ENTITY_ID`](Self::MAX_ENTITY_ID) are refused: [`insert`](EntitySet::insert) returns `false` and
+/// leaves the set as it was.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DenseBitSet {
+    /// Never ends in a zero word, so equal sets have equal words.
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl DenseBitSet {
+    /// The largest ID a `DenseBitSet` holds, so that a set never takes more than 32 MiB. Use a [`RoaringSet`] for
+    /// larger IDs.
+    pub const MAX_ENTITY_ID: EntityId = (1 << 28) - 1;
+
+    /// The word and the bit within it for `entity_id`, which must be at most [`MAX_ENTITY_ID`](Self::MAX_ENTITY_ID).
+    fn position(entity_id: EntityId) -> (usize, u64) {
+        debug_assert!(entity_id <= Self::MAX_ENTITY_ID);
+        ((entity_id / 64) as usize, 1 << (entity_id % 64))
+    }
+
+    fn from_words(mut words: Vec<u64>) -> Self {
+        while words.last() == Some(&0) {
+            words.pop();
+        }
+        let len = words.iter().map(|word| word.count_ones() as usize).sum();
+        Self { words, len }
+    }
+}
+
+impl FromIterator<EntityId> for DenseBitSet {
+    fn from_iter<I: IntoIterator<Item = EntityId>>(entity_ids: I) -> Self {
+        let mut set = Self::default();
+        for entity_id in entity_ids {
+            set.insert(entity_id);
+        }
+        set
+    }
+}
+
+impl EntitySet for DenseBitSet {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn contains(&self, entity_id: EntityId) -> bool {
+        if entity_id > Self::MAX_ENTITY_ID {
+            return false;
+        }
+        let (word, bit) = Self::position(entity_id);
+        self.words.get(word).is_some_and(|word| word & bit != 0)
+    }
+
+    fn insert(&mut self, entity_id: EntityId) -> bool {
+        if entity_id > Self::MAX_ENTITY_ID {
+            return false;
+        }
+        let (word, bit) = Self::position(entity_id);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let inserted = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        self.len += usize::from(inserted);
+        inserted
+    }
+
+    fn remove(&mut self, entity_id: EntityId) -> bool {
+        if !self.contains(entity_id) {
+            return false;
+        }
+        let (word, bit) = Self::position(entity_id);
+        self.words[word] &= !bit;
+        self.len -= 1;
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+        true
+    }
+
+    fn max_entity_id() -> EntityId {
+        Self::MAX_ENTITY_ID
+    }
+
+    fn iter_ids(&self) -> Box<dyn Iterator<Item = EntityId> + '_> {
+        Box::new(self.words.iter().enumerate().flat_map(|(index, &word)| {
+            set_bits(word).map(move |bit| index as EntityId * 64 + EntityId::from(bit))
+        }))
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.words.capacity() * size_of::<u64>()
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        let (longer, shorter) = if self.words.len() >= other.words.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let mut words = longer.words.clone();
+        for (word, other) in words.iter_mut().zip(&shorter.words) {
+            *word |= other;
+        }
+        Self::from_words(words)
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        let words = self
+            .words
+            .iter()
+            .zip(&other.words)
+            .map(|(word, other)| word & other)
+            .collect();
+        Self::from_words(words)
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        let mut words = self.words.clone();
+        for (word, other) in words.iter_mut().zip(&other.words) {
+            *word &= !other;
+        }
+        Self::from_words(words)
+    }
+}
+
+/// The most entities a chunk stores as an array. Above this a bitset, at 8 KB, is smaller.
+const ARRAY_MAX: usize = 4096;
+
+/// The low 16 bits of the entities in one chunk of a [`RoaringSet`], stored as a sorted array when there are at most
+/// [`ARRAY_MAX`] of them and as a bitset otherwise. Never empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Container {
+    Array(Vec<u16>),
+    Bitmap { words: Box<[u64; 1024]>, len: usize },
+}
+
+impl Container {
+    fn from_sorted(lows: Vec<u16>) -> Option<Self> {
+        match lows.len() {
+            0 => None,
+            len if len <= ARRAY_MAX => Some(Self::Array(lows)),
+            len => {
+                let mut words = Box::new([0; 1024]);
+                for low in lows {
+                    words[usize::from(low / 64)] |= 1 << (low % 64);
+                }
+                Some(Self::Bitmap { words, len })
+            }
+        }
+    }
+
+    fn from_words(words: Box<[u64; 1024]>) -> Option<Self> {
+        let len = words.iter().map(|word| word.count_ones() as usize).sum();
+        if len <= ARRAY_MAX {
+            return Self::from_sorted(Self::bits(&words).collect());
+        }
+        Some(Self::Bitmap { words, len })
+    }
+
+    fn bits(words: &[u64; 1024]) -> impl Iterator<Item = u16> + '_ {
+        words.iter().enumerate().flat_map(|(index, &word)| {
+            set_bits(word).map(move |bit| index as u16 * 64 + bit as u16)
+        })
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Array(lows) => lows.len(),
+            Self::Bitmap { len, .. } => *len,
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Self::Array(lows) => lows.capacity() * size_of::<u16>(),
+            Self::Bitmap { .. } => size_of::<[u64; 1024]>(),
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Self::Array(lows) => lows.binary_search(&low).is_ok(),
+            Self::Bitmap { words, .. } => words[usize::from(low / 64)] & (1 << (low % 64)) != 0,
+        }
+    }
+
+    fn insert(&mut self, low: u16) -> bool {
+        match self {
+            Self::Array(lows) => match lows.binary_search(&low) {
+                Ok(_) => false,
+                Err(position) => {
+                    lows.insert(position, low);
+                    if lows.len() > ARRAY_MAX {
+                        *self =
+                            Self::from_sorted(std::mem::take(lows)).expect("the array isn't empty");
+                    }
+                    true
+                }
+            },
+            Self::Bitmap { words, len } => {
+                let (word, bit) = (usize::from(low / 64), 1 << (low % 64));
+                let inserted = words[word] & bit == 0;
+                words[word] |= bit;
+                *len += usize::from(inserted);
+                inserted
+            }
+        }
+    }
+
+    /// Returns whether `low` was in the container, which may be left empty.
+    fn remove(&mut self, low: u16) -> bool {
+        match self {
+            Self::Array(lows) => match lows.binary_search(&low) {
+                Ok(position) => {
+                    lows.remove(position);
+                    true
+                }
+                Err(_) => false,
+            },
+            Self::Bitmap { words, len } => {
+                let (word, bit) = (usize::from(low / 64), 1 << (low % 64));
+                if words[word] & bit == 0 {
+                    return false;
+                }
+                words[word] &= !bit;
+                *len -= 1;
+                if *len == ARRAY_MAX {
+                    *self = Self::Array(Self::bits(words).collect());
+                }
+                true
+            }
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        match self {
+            Self::Array(lows) => Box::new(lows.iter().copied()),
+            Self::Bitmap { words, .. } => Box::new(Self::bits(words)),
+        }
+    }
+
+    fn to_words(&self) -> Box<[u64; 1024]> {
+        match self {
+            Self::Array(lows) => {
+                let mut words = Box::new([0; 1024]);
+                for low in lows {
+                    words[usize::from(low / 64)] |= 1 << (low % 64);
+                }
+                words
+            }
+            Self::Bitmap { words, .. } => words.clone(),
+        }
+    }
+
+    fn union(&self, other: &Self) -> Option<Self> {
+        if let (Self::Array(a), Self::Array(b)) = (self, other) {
+            return Self::from_sorted(sorted_union(a, b));
+        }
+        let mut words = self.to_words();
+        for (word, other) in words.iter_mut().zip(other.to_words().iter()) {
+            *word |= other;
+        }
+        Self::from_words(words)
+    }
+
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Array(a), Self::Array(b)) => Self::from_sorted(sorted_intersection(a, b)),
+            (Self::Array(lows), bitmap) | (bitmap, Self::Array(lows)) => Self::from_sorted(
+                lows.iter()
+                    .copied()
+                    .filter(|low| bitmap.contains(*low))
+                    .collect(),
+            ),
+            (Self::Bitmap { words: a, .. }, Self::Bitmap { words: b, .. }) => {
+                let mut words = a.clone();
+                for (word, other) in words.iter_mut().zip(b.iter()) {
+                    *word &= other;
+                }
+                Self::from_words(words)
+            }
+        }
+    }
+
+    fn difference(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Array(a), Self::Array(b)) => Self::from_sorted(sorted_difference(a, b)),
+            (Self::Array(lows), other) => Self::from_sorted(
+                lows.iter()
+                    .copied()
+                    .filter(|low| !other.contains(*low))
+                    .collect(),
+            ),
+            (Self::Bitmap { words, .. }, other) => {
+                let mut words = words.clone();
+                for (word, other) in words.iter_mut().zip(other.to_words().iter()) {
+                    *word &= !other;
+                }
+                Self::from_words(words)
+            }
+        }
+    }
+}
+
+/// A roaring-style compressed bitmap: the entities are grouped by their high 48 bits into chunks, each stored in
+/// whichever of an array or a bitset is smaller.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoaringSet {
+    /// Sorted by the high bits, with no empty containers.
+    containers: Vec<(u64, Container)>,
+    len: usize,
+}
+
+impl RoaringSet {
+    fn split(entity_id: EntityId) -> (u64, u16) {
+        (entity_id >> 16, entity_id as u16)
+    }
+
+    fn container(&self, high: u64) -> Result<usize, usize> {
+        self.containers.binary_search_by_key(&high, |(key, _)| *key)
+    }
+
+    /// Merges the containers of `self` and `other` chunk by chunk. `both` combines two containers with the same high
+    /// bits; containers only in `self` or only in `other` are kept if `keep_left` or `keep_right`.
+    fn merge(
+        &self,
+        other: &Self,
+        keep_left: bool,
+        keep_right: bool,
+        both: impl Fn(&Container, &Container) -> Option<Container>,
+    ) -> Self {
+        let mut containers = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.containers.len() || j < other.containers.len() {
+            let left = self.containers.get(i);
+            let right = other.containers.get(j);
+            match (left, right) {
+                (Some((a, left)), Some((b, right))) if a == b => {
+                    containers.extend(both(left, right).map(|container| (*a, container)));
+                    i += 1;
+                    j += 1;
+                }
+                (Some((a, left)), Some((b, _))) if a < b => {
+                    if keep_left {
+                        containers.push((*a, left.clone()));
+                    }
+                    i += 1;
+                }
+                (Some((a, left)), None) => {
+                    if keep_left {
+                        containers.push((*a, left.clone()));
+                    }
+                    i += 1;
+                }
+                (_, Some((b, right))) => {
+                    if keep_right {
+                        containers.push((*b, right.clone()));
+                    }
+                    j += 1;
+                }
+                (None, None) => unreachable!("the loop ends when both are exhausted"),
+            }
+        }
+        let len = containers
+            .iter()
+            .map(|(_, container)| container.len())
+            .sum();
+        Self { containers, len }
+    }
+}
+
+impl FromIterator<EntityId> for RoaringSet {
+    fn from_iter<I: IntoIterator<Item = EntityId>>(entity_ids: I) -> Self {
+        let mut set = Self::default();
+        for entity_id in entity_ids {
+            set.insert(entity_id);
+        }
+        set
+    }
+}
+
+impl EntitySet for RoaringSet {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn contains(&self, entity_id: EntityId) -> bool {
+        let (high, low) = Self::split(entity_id);
+        self.container(high)
+            .is_ok_and(|index| self.containers[index].1.contains(low))
+    }
+
+    fn insert(&mut self, entity_id: EntityId) -> bool {
+        let (high, low) = Self::split(entity_id);
+        let inserted = match self.container(high) {
+            Ok(index) => self.containers[index].1.insert(low),
+            Err(index) => {
+                self.containers
+                    .insert(index, (high, Container::Array(vec![low])));
+                true
+            }
+        };
+        self.len += usize::from(inserted);
+        inserted
+    }
+
+    fn remove(&mut self, entity_id: EntityId) -> bool {
+        let (high, low) = Self::split(entity_id);
+        let Ok(index) = self.container(high) else {
+            return false;
+        };
+        let container = &mut self.containers[index].1;
+        let removed = container.remove(low);
+        if container.len() == 0 {
+            self.containers.remove(index);
+        }
+        self.len -= usize::from(removed);
+        removed
+    }
+
+    fn iter_ids(&self) -> Box<dyn Iterator<Item = EntityId> + '_> {
+        Box::new(self.containers.iter().flat_map(|(high, container)| {
+            container
+                .iter()
+                .map(move |low| high << 16 | EntityId::from(low))
+        }))
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.containers.capacity() * size_of::<(u64, Container)>()
+            + self
+                .containers
+                .iter()
+                .map(|(_, container)| container.heap_bytes())
+                .sum::<usize>()
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        self.merge(other, true, true, Container::union)
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        self.merge(other, false, false, Container::intersection)
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        self.merge(other, true, false, Container::difference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::hashing::one_shot_64;
+
+    /// `count` pseudo-random IDs below `max`.
+    fn ids(seed: u64, count: u64, max: u64) -> Vec<EntityId> {
+        (0..count).map(|i| one_shot_64(&(seed, i)) % max).collect()
+    }
+
+    fn sorted(set: &dyn EntitySet) -> Vec<EntityId> {
+        let mut entity_ids: Vec<_> = set.iter_ids().collect();
+        entity_ids.sort_unstable();
+        entity_ids
+    }
+
+    /// Checks every operation of `E` against a `BTreeSet`.
+    fn agrees_with_btree_set<E: EntitySet + FromIterator<EntityId> + Clone + PartialEq>(max: u64) {
+        let (a, b) = (ids(1, 6000, max), ids(2, 3000, max));
+        let (set_a, set_b): (E, E) = (a.iter().copied().collect(), b.iter().copied().collect());
+        let (tree_a, tree_b): (BTreeSet<_>, BTreeSet<_>) =
+            (a.iter().copied().collect(), b.iter().copied().collect());
+
+        assert_eq!(set_a.len(), tree_a.len());
+        assert_eq!(sorted(&set_a), tree_a.iter().copied().collect::<Vec<_>>());
+        assert_eq!(
+            sorted(&set_a.union(&set_b)),
+            tree_a.union(&tree_b).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            sorted(&set_a.intersection(&set_b)),
+            tree_a.intersection(&tree_b).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            sorted(&set_a.difference(&set_b)),
+            tree_a.difference(&tree_b).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(set_a.union(&set_b).len(), tree_a.union(&tree_b).count());
+
+        // Removing every element of `b` one at a time is the same as the difference.
+        let mut removed = set_a.clone();
+        let mut tree_removed = tree_a.clone();
+        for &entity_id in &b {
+            assert_eq!(removed.remove(entity_id), tree_removed.remove(&entity_id));
+            assert!(!removed.contains(entity_id));
+        }
+        assert_eq!(removed.len(), tree_removed.len());
+        assert!(removed == set_a.difference(&set_b));
+
+        // And inserting them again is the same as the union.
+        for &entity_id in &b {
+            assert_eq!(removed.insert(entity_id), tree_removed.insert(entity_id));
+        }
+        assert!(removed == set_a.union(&set_b));
+    }
+
+    #[test]
+    fn hash_sets_agree_with_btree_set() {
+        agrees_with_btree_set::<FastHashSet<EntityId>>(10_000);
+    }
+
+    #[test]
+    fn sorted_vec_sets_agree_with_btree_set() {
+        agrees_with_btree_set::<SortedVecSet>(10_000);
+    }
+
+    #[test]
+    fn dense_bit_sets_agree_with_btree_set() {
+        agrees_with_btree_set::<DenseBitSet>(10_000);
+    }
+
+    #[test]
+    fn roaring_sets_agree_with_btree_set() {
+        // Dense enough for bitmap containers, and sparse enough for arrays.
+        agrees_with_btree_set::<RoaringSet>(10_000);
+        agrees_with_btree_set::<RoaringSet>(1 << 40);
+    }
+
+    #[test]
+    fn roaring_containers_switch_representation() {
+        let mut set: RoaringSet = (0..ARRAY_MAX as EntityId).collect();
+        assert!(matches!(set.containers[0].1, Container::Array(_)));
+        set.insert(1 << 20);
+        set.insert(ARRAY_MAX as EntityId);
+        assert!(matches!(set.containers[0].1, Container::Bitmap { .. }));
+        assert_eq!(set.containers.len(), 2);
+
+        set.remove(0);
+        assert!(matches!(set.containers[0].1, Container::Array(_)));
+        set.remove(1 << 20);
+        assert_eq!(set.containers.len(), 1);
+        assert_eq!(set.len(), ARRAY_MAX);
+        assert_eq!(set, (1..=ARRAY_MAX as EntityId).collect());
+    }
+
+    #[test]
+    fn dense_bit_sets_trim_trailing_words() {
+        let mut set: DenseBitSet = [1, 1000].into_iter().collect();
+        set.remove(1000);
+        assert_eq!(set, [1].into_iter().collect());
+        assert_eq!(set.words.len(), 1);
+    }
+
+    #[test]
+    fn heap_bytes_follow_the_representation() {
+        let ids = || (0..5000).map(|entity_id| entity_id * 3);
+        let sorted: SortedVecSet = ids().collect();
+        assert_eq!(sorted.heap_bytes(), sorted.0.capacity() * 8);
+        let dense: DenseBitSet = ids().collect();
+        assert_eq!(dense.heap_bytes(), dense.words.capacity() * 8);
+        assert!(dense.heap_bytes() >= 14997 / 8);
+        let hashed: FastHashSet<EntityId> = ids().collect();
+        assert_eq!(hashed.heap_bytes(), hashed.allocation_size());
+
+        // One chunk of 5000 entities is a bitmap, and the next, with one, an array.
+        let mut roaring: RoaringSet = ids().collect();
+        roaring.insert(1 << 16);
+        let containers = roaring.containers.capacity() * size_of::<(u64, Container)>();
+        let Container::Array(lows) = &roaring.containers[1].1 else {
+            panic!("the chunk with one entity isn't an array");
+        };
+        assert_eq!(
+            roaring.heap_bytes(),
+            containers + 8192 + lows.capacity() * 2
+        );
+        assert_eq!(RoaringSet::new().heap_bytes(), 0);
+    }
+
+    #[test]
+    fn dense_bit_sets_refuse_ids_out_of_range() {
+        let mut set = DenseBitSet::new();
+        assert!(set.insert(DenseBitSet::MAX_ENTITY_ID));
+        assert!(!set.insert(DenseBitSet::MAX_ENTITY_ID + 1));
+        assert!(!set.insert(EntityId::MAX));
+        assert!(!set.contains(EntityId::MAX));
+        assert!(!set.remove(EntityId::MAX));
+        assert_eq!(sorted(&set), [DenseBitSet::MAX_ENTITY_ID]);
+        assert_eq!(
+            set.words.len() as u64,
+            (DenseBitSet::MAX_ENTITY_ID + 1) / 64
+        );
+    }
+}
@@ -0,0 +1,914 @@
+/*!
+
+# Serializing Type-Erased Indexes
+
+A simulation that keeps a `Vec<BxIndex>` wants to checkpoint every index without knowing what
+each one's `T` is, and to load them back the same way. The type-erased API can't do this alone:
+serializing needs `T: Serialize`, and the blanket `impl TypeErasedIndex for Index<T>` can't require
+that of every `T`. Deserializing is worse, because there's no `Index<T>` yet to call anything on.
+
+The fix is a registry of per-type codecs. Each value type that should be serializable is
+registered once, under a _stable type name_ of your choosing:
+
+```rust,ignore
+register_index_codec::<Position>("sim::Position");
+```
+
+The name is written into every checkpoint, and it's how [`deserialize_index`] finds the codec that
+rebuilds an `Index<Position>`. (It can't be [`std::any::type_name`], which isn't guaranteed to be
+stable across compiler versions, let alone across a rename.) [`TypeErasedIndex::serialize_into`]
+looks its codec up by `TypeId` instead and fails with [`CodecError::UnregisteredType`], wrapped in
+an `IndexError`, if there isn't one.
+
+A checkpoint is the type name, the [`IndexKind`], the seed of the index's [`Xxh3BuildHasher`], and
+then each value with its entity IDs, in one of the [`IndexFormat`]s enabled by the `serde-json` and
+`serde-postcard` features. In JSON it looks like this:
+
+```json
+{"type":"sim::Position","kind":"Index","seed":0,"entries":[[[1,2],[7,9]],[[0,0],[3]]]}
+```
+
+Entries are ordered by hash and entity IDs are sorted, so the same index always produces the same
+bytes. A binary checkpoint is prefixed with its length, and a JSON one ends with its closing brace,
+so several checkpoints can be written to one stream and read back one by one.
+
+Deserializing rebuilds the same kind of index with the same seed, so the hashes come back
+unchanged. Rebuilding an [`OrderedIndex`] needs `T: Ord`, and a [`MultiIndex`] needs its key type,
+so those kinds have their own registration functions, [`register_ordered_index_codec`] and
+[`register_multi_index_codec`]. An index the codec can't rebuild isn't written at all: one with
+another hasher, or an `Index<T, S, E>` with a set type other than the default, fails to serialize
+with [`CodecError::Unsupported`].
+
+[`TypeErasedIndex::serialize_into`]: super::type_erased_api::TypeErasedIndex::serialize_into
+
+*/
+
+use std::{
+    any::{Any, TypeId},
+    fmt::{Display, Formatter},
+    hash::Hash,
+    io::{Read, Write},
+    sync::{LazyLock, RwLock},
+};
+
+use serde::{Serialize, Serializer, de::DeserializeOwned, ser::SerializeStruct};
+
+use super::{
+    entity_set::EntitySet,
+    multi_index::{CanonicalKey, MultiIndex, MultiKey},
+    ordered_index::OrderedIndex,
+    type_erased_api::{BxIndex, EntityId, Index},
+};
+use crate::hashing::{FastHashMap, FastHashSet, Xxh3BuildHasher};
+
+/// The formats an index can be serialized in. Each is enabled by a cargo feature.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IndexFormat {
+    /// JSON via `serde_json`, for checkpoints a human might read. Needs `serde-json`.
+    #[cfg(feature = "serde-json")]
+    Json,
+    /// The compact `postcard` wire format. Needs `serde-postcard`.
+    #[cfg(feature = "serde-postcard")]
+    Binary,
+}
+
+/// The kinds of index a checkpoint can hold.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IndexKind {
+    /// An [`Index<T>`].
+    Index,
+    /// An [`OrderedIndex<T>`].
+    Ordered,
+    /// A [`MultiIndex<K>`], whose values are [`CanonicalKey<K>`]s.
+    Multi,
+}
+
+impl IndexKind {
+    /// The name written into checkpoints.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Index => "Index",
+            Self::Ordered => "OrderedIndex",
+            Self::Multi => "MultiIndex",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, CodecError> {
+        [Self::Index, Self::Ordered, Self::Multi]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| CodecError::Invalid(format!("unknown index kind `{name}`")))
+    }
+}
+
+/// What a checkpoint records about an index besides its entries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct IndexShape {
+    kind: IndexKind,
+    seed: u64,
+}
+
+impl IndexShape {
+    /// The shape of an index of the given kind with hasher `S` and sets `E`, if a checkpoint can restore it.
+    pub(crate) fn of<S: Any, E: Any>(
+        kind: IndexKind,
+        build_hasher: &S,
+    ) -> Result<Self, CodecError> {
+        let seed = (build_hasher as &dyn Any)
+            .downcast_ref::<Xxh3BuildHasher>()
+            .map(Xxh3BuildHasher::seed)
+            .ok_or_else(|| {
+                CodecError::Unsupported(format!(
+                    "the hasher `{}` has no seed to record, only `Xxh3BuildHasher` does",
+                    std::any::type_name::<S>()
+                ))
+            })?;
+        if TypeId::of::<E>() != TypeId::of::<FastHashSet<EntityId>>() {
+            return Err(CodecError::Unsupported(format!(
+                "indexes are restored with `FastHashSet` sets, not `{}`",
+                std::any::type_name::<E>()
+            )));
+        }
+        Ok(Self { kind, seed })
+    }
+}
+
+/// Why an index couldn't be serialized or deserialized.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The index's value type, named here by [`std::any::type_name`], has no registered codec.
+    UnregisteredType(&'static str),
+    /// The checkpoint's type name has no registered codec.
+    UnknownTypeName(String),
+    /// The checkpoint decoded, but isn't a valid index.
+    Invalid(String),
+    /// The index can't be written, or the checkpoint's kind of index can't be rebuilt, by the registered codecs.
+    Unsupported(String),
+    Io(std::io::Error),
+    #[cfg(feature = "serde-json")]
+    Json(serde_json::Error),
+    #[cfg(feature = "serde-postcard")]
+    Binary(postcard::Error),
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnregisteredType(type_name) => {
+                write!(f, "no index codec is registered for the type `{type_name}`")
+            }
+            Self::UnknownTypeName(name) => {
+                write!(f, "no index codec is registered under the name `{name}`")
+            }
+            Self::Invalid(reason) => write!(f, "invalid index checkpoint: {reason}"),
+            Self::Unsupported(reason) => write!(f, "unsupported index checkpoint: {reason}"),
+            Self::Io(error) => write!(f, "I/O error: {error}"),
+            #[cfg(feature = "serde-json")]
+            Self::Json(error) => write!(f, "JSON error: {error}"),
+            #[cfg(feature = "serde-postcard")]
+            Self::Binary(error) => write!(f, "postcard error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            #[cfg(feature = "serde-json")]
+            Self::Json(error) => Some(error),
+            #[cfg(feature = "serde-postcard")]
+            Self::Binary(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CodecError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(feature = "serde-json")]
+impl From<serde_json::Error> for CodecError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+#[cfg(feature = "serde-postcard")]
+impl From<postcard::Error> for CodecError {
+    fn from(error: postcard::Error) -> Self {
+        Self::Binary(error)
+    }
+}
+
+/// The contents of an index as the type-erased API sees them, in the order they're written.
+pub(crate) type ErasedEntries<'a> = [(&'a dyn Any, &'a dyn EntitySet)];
+
+/// Decodes one value of a registered type, see [`encode_key`].
+type KeyDecoder = fn(&[u8], IndexFormat) -> Result<Box<dyn Any>, CodecError>;
+
+/// Writes a checkpoint with the given type name and shape.
+type EntriesSerializer =
+    fn(&str, IndexShape, &ErasedEntries<'_>, &mut dyn Write, IndexFormat) -> Result<(), CodecError>;
+
+/// A checkpoint's entries once decoded, a `Vec<(T, Vec<EntityId>)>`.
+type DecodedEntries = Box<dyn Any>;
+
+/// Rebuilds one kind of index from its decoded entries and the hasher they were written with.
+type IndexBuilder = fn(DecodedEntries, Xxh3BuildHasher) -> Result<BxIndex, CodecError>;
+
+/// The monomorphic functions for one value type.
+#[derive(Copy, Clone)]
+pub(crate) struct IndexCodec {
+    type_name: &'static str,
+    type_id: TypeId,
+    serialize: EntriesSerializer,
+    #[cfg(feature = "serde-json")]
+    from_json: fn(serde_json::Value) -> Result<DecodedEntries, CodecError>,
+    #[cfg(feature = "serde-postcard")]
+    from_postcard: fn(&[u8]) -> Result<DecodedEntries, CodecError>,
+    decode_key: KeyDecoder,
+}
+
+impl IndexCodec {
+    /// Writes `entries`, whose values must be of the type this codec was registered for.
+    pub(crate) fn serialize(
+        &self,
+        shape: IndexShape,
+        entries: &ErasedEntries<'_>,
+        writer: &mut dyn Write,
+        format: IndexFormat,
+    ) -> Result<(), CodecError> {
+        (self.serialize)(self.type_name, shape, entries, writer, format)
+    }
+}
+
+#[derive(Default)]
+struct CodecRegistry {
+    by_name: FastHashMap<&'static str, IndexCodec>,
+    by_type: FastHashMap<TypeId, IndexCodec>,
+    /// The kinds of index each registered type can be rebuilt as.
+    builders: FastHashMap<(TypeId, IndexKind), IndexBuilder>,
+}
+
+static CODECS: LazyLock<RwLock<CodecRegistry>> = LazyLock::new(Default::default);
+
+/// Registers the codec for `Index<T>` under the stable `type_name`. Registering the same type under the same name
+/// again does nothing.
+///
+/// Panics if `type_name` is already registered for another type, or `T` under another name, because either would make
+/// checkpoints ambiguous.
+pub fn register_index_codec<T>(type_name: &'static str)
+where
+    T: Serialize + DeserializeOwned + Hash + Eq + Clone + Any,
+{
+    register_builder::<T>(type_name, IndexKind::Index, build_index::<T>);
+}
+
+/// Registers the codec for `T` like [`register_index_codec`], so that checkpoints of an `Index<T>` or an
+/// [`OrderedIndex<T>`] can both be read back.
+pub fn register_ordered_index_codec<T>(type_name: &'static str)
+where
+    T: Serialize + DeserializeOwned + Ord + Hash + Clone + Any,
+{
+    register_builder::<T>(type_name, IndexKind::Index, build_index::<T>);
+    register_builder::<T>(type_name, IndexKind::Ordered, build_ordered_index::<T>);
+}
+
+/// Registers the codec for the values of a [`MultiIndex<K>`], [`CanonicalKey<K>`], under the stable `type_name`, so
+/// that its checkpoints can be read back.
+pub fn register_multi_index_codec<K>(type_name: &'static str)
+where
+    K: MultiKey + Serialize + DeserializeOwned,
+{
+    register_builder::<CanonicalKey<K>>(
+        type_name,
+        IndexKind::Index,
+        build_index::<CanonicalKey<K>>,
+    );
+    register_builder::<CanonicalKey<K>>(type_name, IndexKind::Multi, build_multi_index::<K>);
+}
+
+/// Registers the codec for `T` if it isn't already, and the builder for one kind of index of `T`.
+fn register_builder<T>(type_name: &'static str, kind: IndexKind, builder: IndexBuilder)
+where
+    T: Serialize + DeserializeOwned + Any,
+{
+    let type_id = TypeId::of::<T>();
+    let codec = IndexCodec {
+        type_name,
+        type_id,
+        serialize: serialize_entries::<T>,
+        #[cfg(feature = "serde-json")]
+        from_json: entries_from_json::<T>,
+        #[cfg(feature = "serde-postcard")]
+        from_postcard: entries_from_postcard::<T>,
+        decode_key: decode_boxed_key::<T>,
+    };
+    let mut registry = CODECS.write().unwrap_or_else(|error| error.into_inner());
+    if let Some(existing) = registry.by_type.get(&type_id) {
+        assert_eq!(
+            existing.type_name,
+            type_name,
+            "`{}` is already registered under another name",
+            std::any::type_name::<T>()
+        );
+    } else {
+        assert!(
+            !registry.by_name.contains_key(type_name),
+            "the name `{type_name}` is already registered for another type"
+        );
+        registry.by_name.insert(type_name, codec);
+        registry.by_type.insert(type_id, codec);
+    }
+    registry.builders.insert((type_id, kind), builder);
+}
+
+/// The stable name `T` was registered under, if it was.
+pub fn registered_type_name<T: Any>() -> Option<&'static str> {
+    codec_for_type(TypeId::of::<T>()).map(|codec| codec.type_name)
+}
+
+pub(crate) fn codec_for_type(type_id: TypeId) -> Option<IndexCodec> {
+    CODECS
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .by_type
+        .get(&type_id)
+        .copied()
+}
+
+/// Writes a checkpoint of an index of the given shape holding `entries`, in `format` with the codec registered for
+/// `T`, in the order given.
+pub(crate) fn serialize_values<'a, T: Any>(
+    shape: IndexShape,
+    entries: impl IntoIterator<Item = (&'a T, &'a dyn EntitySet)>,
+    writer: &mut dyn Write,
+    format: IndexFormat,
+) -> Result<(), CodecError> {
+    let codec = codec_for_type(TypeId::of::<T>())
+        .ok_or(CodecError::UnregisteredType(std::any::type_name::<T>()))?;
+    let entries: Vec<_> = entries
+        .into_iter()
+        .map(|(value, set)| (value as &dyn Any, set))
+        .collect();
+    codec.serialize(shape, &entries, writer, format)
+}
+
+/// Serializes a single value in `format`, the way it appears in a checkpoint. This is how a caller that knows `T`
+/// hands a key to code that doesn't, such as the bounds of a
+/// [`RangeKey::Serialized`](super::ordered_index::RangeKey::Serialized).
+pub fn encode_key<T: Serialize>(key: &T, format: IndexFormat) -> Result<Vec<u8>, CodecError> {
+    Ok(match format {
+        #[cfg(feature = "serde-json")]
+        IndexFormat::Json => serde_json::to_vec(key)?,
+        #[cfg(feature = "serde-postcard")]
+        IndexFormat::Binary => postcard::to_allocvec(key)?,
+    })
+}
+
+/// Decodes a single value written by [`encode_key`], with the codec registered for `T`.
+pub(crate) fn decode_key<T: Any>(bytes: &[u8], format: IndexFormat) -> Result<T, CodecError> {
+    let codec = codec_for_type(TypeId::of::<T>())
+        .ok_or(CodecError::UnregisteredType(std::any::type_name::<T>()))?;
+    let key = (codec.decode_key)(bytes, format)?;
+    Ok(*key
+        .downcast::<T>()
+        .expect("codecs are looked up by the `TypeId` of the key"))
+}
+
+fn codec_for_name(type_name: &str) -> Result<IndexCodec, CodecError> {
+    CODECS
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .by_name
+        .get(type_name)
+        .copied()
+        .ok_or_else(|| CodecError::UnknownTypeName(type_name.to_string()))
+}
+
+/// Rebuilds the index a checkpoint holds from its decoded `entries`.
+fn build(
+    codec: IndexCodec,
+    kind: IndexKind,
+    seed: u64,
+    entries: DecodedEntries,
+) -> Result<BxIndex, CodecError> {
+    let builder = CODECS
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .builders
+        .get(&(codec.type_id, kind))
+        .copied()
+        .ok_or_else(|| {
+            CodecError::Unsupported(format!(
+                "`{}` isn't registered to be rebuilt as a `{}`",
+                codec.type_name,
+                kind.name()
+            ))
+        })?;
+    builder(entries, Xxh3BuildHasher::new(seed))
+}
+
+/// Reads a checkpoint written by [`TypeErasedIndex::serialize_into`] in `format`, and rebuilds the index with the
+/// codec registered under the type name it contains. Reads nothing past the end of the checkpoint, so checkpoints
+/// written one after another to the same stream read back one call at a time.
+///
+/// [`TypeErasedIndex::serialize_into`]: super::type_erased_api::TypeErasedIndex::serialize_into
+pub fn deserialize_index(
+    reader: &mut dyn Read,
+    format: IndexFormat,
+) -> Result<BxIndex, CodecError> {
+    match format {
+        #[cfg(feature = "serde-json")]
+        IndexFormat::Json => {
+            // Unlike `serde_json::from_reader`, a stream deserializer stops at the end of the first value.
+            let mut checkpoint: serde_json::Value = serde_json::Deserializer::from_reader(reader)
+                .into_iter()
+                .next()
+                .ok_or_else(|| CodecError::Invalid("no checkpoint left to read".to_string()))??;
+            let field = |name: &str| {
+                checkpoint
+                    .get(name)
+                    .ok_or_else(|| CodecError::Invalid(format!("missing the field `{name}`")))
+            };
+            let string_field = |name: &str| {
+                field(name)?
+                    .as_str()
+                    .ok_or_else(|| CodecError::Invalid(format!("`{name}` must be a string")))
+            };
+            let codec = codec_for_name(string_field("type")?)?;
+            let kind = IndexKind::from_name(string_field("kind")?)?;
+            let seed = field("seed")?
+                .as_u64()
+                .ok_or_else(|| CodecError::Invalid("the seed isn't a `u64`".to_string()))?;
+            let entries = (codec.from_json)(checkpoint["entries"].take())?;
+            build(codec, kind, seed, entries)
+        }
+        #[cfg(feature = "serde-postcard")]
+        IndexFormat::Binary => {
+            let len = read_frame_len(reader)?;
+            let mut bytes = Vec::new();
+            reader.take(len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != len {
+                return Err(CodecError::Invalid(
+                    "the checkpoint is truncated".to_string(),
+                ));
+            }
+            let ((type_name, kind, seed), entries) =
+                postcard::take_from_bytes::<(&str, &str, u64)>(&bytes)?;
+            let codec = codec_for_name(type_name)?;
+            let entries = (codec.from_postcard)(entries)?;
+            build(codec, IndexKind::from_name(kind)?, seed, entries)
+        }
+    }
+}
+
+/// The serialized form of an index. Written as a struct, so in `postcard` it's just the fields in order.
+struct Checkpoint<'a, T> {
+    type_name: &'a str,
+    shape: IndexShape,
+    entries: Vec<(&'a T, Vec<EntityId>)>,
+}
+
+impl<T: Serialize> Serialize for Checkpoint<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut checkpoint = serializer.serialize_struct("IndexCheckpoint", 4)?;
+        checkpoint.serialize_field("type", self.type_name)?;
+        checkpoint.serialize_field("kind", self.shape.kind.name())?;
+        checkpoint.serialize_field("seed", &self.shape.seed)?;
+        checkpoint.serialize_field("entries", &self.entries)?;
+        checkpoint.end()
+    }
+}
+
+fn serialize_entries<T: Serialize + Any>(
+    type_name: &str,
+    shape: IndexShape,
+    entries: &ErasedEntries<'_>,
+    writer: &mut dyn Write,
+    format: IndexFormat,
+) -> Result<(), CodecError> {
+    let entries = entries
+        .iter()
+        .map(|(value, set)| {
+            let value = value
+                .downcast_ref::<T>()
+                .expect("codecs are looked up by the `TypeId` of the index's values");
+            let mut entity_ids: Vec<EntityId> = set.iter_ids().collect();
+            entity_ids.sort_unstable();
+            (value, entity_ids)
+        })
+        .collect();
+    let checkpoint = Checkpoint {
+        type_name,
+        shape,
+        entries,
+    };
+    match format {
+        #[cfg(feature = "serde-json")]
+        IndexFormat::Json => serde_json::to_writer(writer, &checkpoint)?,
+        #[cfg(feature = "serde-postcard")]
+        IndexFormat::Binary => {
+            // Prefixed with its length, so a reader can stop at its end.
+            let bytes = postcard::to_stdvec(&checkpoint)?;
+            postcard::to_io(&(bytes.len() as u64), &mut *writer)?;
+            writer.write_all(&bytes)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the length prefix of a binary checkpoint, a `postcard` varint, one byte at a time so that nothing after it
+/// is consumed.
+#[cfg(feature = "serde-postcard")]
+fn read_frame_len(reader: &mut dyn Read) -> Result<u64, CodecError> {
+    // A varint `u64` is at most 10 bytes, each but the last with its high bit set.
+    let mut prefix = Vec::with_capacity(10);
+    loop {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        prefix.push(byte[0]);
+        if byte[0] & 0x80 == 0 || prefix.len() == 10 {
+            break;
+        }
+    }
+    Ok(postcard::from_bytes(&prefix)?)
+}
+
+/// The entries a codec decoded, as boxed by [`entries_from_json`] or [`entries_from_postcard`].
+fn unbox_entries<T: Any>(entries: DecodedEntries) -> Vec<(T, Vec<EntityId>)> {
+    *entries
+        .downcast()
+        .expect("builders are looked up by the `TypeId` of the decoded values")
+}
+
+fn appears_twice<T>() -> CodecError {
+    CodecError::Invalid(format!(
+        "a value of type `{}` appears twice",
+        std::any::type_name::<T>()
+    ))
+}
+
+fn has_no_entities<T>() -> CodecError {
+    CodecError::Invalid(format!(
+        "a value of type `{}` has no entities",
+        std::any::type_name::<T>()
+    ))
+}
+
+fn build_index<T: Hash + Eq + Clone + Any>(
+    entries: DecodedEntries,
+    build_hasher: Xxh3BuildHasher,
+) -> Result<BxIndex, CodecError> {
+    let mut index: Index<T> = Index::with_hasher(build_hasher);
+    for (value, entity_ids) in unbox_entries::<T>(entries) {
+        if index.get(&value).is_some() {
+            return Err(appears_twice::<T>());
+        }
+        if entity_ids.is_empty() {
+            return Err(has_no_entities::<T>());
+        }
+        index
+            .insert_value(value, entity_ids.into_iter().collect())
+            .map_err(|error| CodecError::Invalid(error.to_string()))?;
+    }
+    Ok(Box::new(index))
+}
+
+fn build_ordered_index<T: Ord + Hash + Clone + Any>(
+    entries: DecodedEntries,
+    build_hasher: Xxh3BuildHasher,
+) -> Result<BxIndex, CodecError> {
+    let mut index = OrderedIndex::<T>::with_hasher(build_hasher);
+    for (value, entity_ids) in unbox_entries::<T>(entries) {
+        if index.get(&value).is_some() {
+            return Err(appears_twice::<T>());
+        }
+        if entity_ids.is_empty() {
+            return Err(has_no_entities::<T>());
+        }
+        index
+            .insert_value(value, entity_ids.into_iter().collect())
+            .map_err(|error| CodecError::Invalid(error.to_string()))?;
+    }
+    Ok(Box::new(index))
+}
+
+fn build_multi_index<K: MultiKey>(
+    entries: DecodedEntries,
+    build_hasher: Xxh3BuildHasher,
+) -> Result<BxIndex, CodecError> {
+    let mut index = MultiIndex::<K>::with_hasher(build_hasher);
+    for (CanonicalKey(key), entity_ids) in unbox_entries::<CanonicalKey<K>>(entries) {
+        if index.get(&key).is_some() {
+            return Err(appears_twice::<K>());
+        }
+        if entity_ids.is_empty() {
+            return Err(has_no_entities::<K>());
+        }
+        for entity_id in entity_ids {
+            index
+                .insert_entity(&key, entity_id)
+                .map_err(|error| CodecError::Invalid(error.to_string()))?;
+        }
+    }
+    Ok(Box::new(index))
+}
+
+fn decode_boxed_key<T: DeserializeOwned + Any>(
+    bytes: &[u8],
+    format: IndexFormat,
+) -> Result<Box<dyn Any>, CodecError> {
+    let key: T = match format {
+        #[cfg(feature = "serde-json")]
+        IndexFormat::Json => serde_json::from_slice(bytes)?,
+        #[cfg(feature = "serde-postcard")]
+        IndexFormat::Binary => postcard::from_bytes(bytes)?,
+    };
+    Ok(Box::new(key))
+}
+
+#[cfg(feature = "serde-json")]
+fn entries_from_json<T: DeserializeOwned + Any>(
+    entries: serde_json::Value,
+) -> Result<DecodedEntries, CodecError> {
+    let entries: Vec<(T, Vec<EntityId>)> = serde_json::from_value(entries)?;
+    Ok(Box::new(entries))
+}
+
+#[cfg(feature = "serde-postcard")]
+fn entries_from_postcard<T: DeserializeOwned + Any>(
+    entries: &[u8],
+) -> Result<DecodedEntries, CodecError> {
+    let entries: Vec<(T, Vec<EntityId>)> = postcard::from_bytes(entries)?;
+    Ok(Box::new(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{
+        hashing::TruncatingBuildHasher,
+        type_erasure::{
+            entity_set::SortedVecSet,
+            type_erased_api::{HashValue, IndexError, TypeErasedIndex},
+        },
+    };
+
+    /// A private type, so that no other test registers it.
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    struct Position(i32, i32);
+
+    fn checkpoint(index: &dyn TypeErasedIndex, format: IndexFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        index.serialize_into(&mut bytes, format).unwrap();
+        bytes
+    }
+
+    fn sets(index: &dyn TypeErasedIndex) -> Vec<(HashValue, Vec<EntityId>)> {
+        let mut sets: Vec<_> = index
+            .iter_hashes()
+            .map(|(hash, set)| {
+                let mut entity_ids: Vec<_> = set.iter_ids().collect();
+                entity_ids.sort();
+                (hash, entity_ids)
+            })
+            .collect();
+        sets.sort();
+        sets
+    }
+
+    fn formats() -> Vec<IndexFormat> {
+        vec![
+            #[cfg(feature = "serde-json")]
+            IndexFormat::Json,
+            #[cfg(feature = "serde-postcard")]
+            IndexFormat::Binary,
+        ]
+    }
+
+    #[test]
+    fn indexes_round_trip_without_knowing_their_type() {
+        register_index_codec::<Position>("tests::Position");
+        register_index_codec::<Position>("tests::Position");
+        register_index_codec::<String>("String");
+        assert_eq!(registered_type_name::<Position>(), Some("tests::Position"));
+
+        let mut positions = Index::<Position>::new();
+        positions.insert_entity(&Position(1, 2), 9).unwrap();
+        positions.insert_entity(&Position(1, 2), 7).unwrap();
+        positions.insert_entity(&Position(0, 0), 3).unwrap();
+        let mut names = Index::<String>::new();
+        names.insert_entity(&"Al".to_string(), 1).unwrap();
+        let indexes: Vec<BxIndex> = vec![Box::new(positions), Box::new(names)];
+
+        for format in formats() {
+            for index in &indexes {
+                let bytes = checkpoint(index.as_ref(), format);
+                let restored = deserialize_index(&mut bytes.as_slice(), format).unwrap();
+                assert_eq!(sets(restored.as_ref()), sets(index.as_ref()));
+                // Checkpoints are deterministic.
+                assert_eq!(checkpoint(restored.as_ref(), format), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn checkpoints_share_a_stream() {
+        register_index_codec::<Position>("tests::Position");
+        register_index_codec::<String>("String");
+        let mut positions = Index::<Position>::new();
+        positions.insert_entity(&Position(1, 2), 9).unwrap();
+        let mut names = Index::<String>::new();
+        names.insert_entity(&"Al".to_string(), 1).unwrap();
+        names.insert_entity(&"Bo".to_string(), 2).unwrap();
+
+        for format in formats() {
+            let mut stream = Vec::new();
+            positions.serialize_into(&mut stream, format).unwrap();
+            names.serialize_into(&mut stream, format).unwrap();
+
+            let mut reader = stream.as_slice();
+            let first = deserialize_index(&mut reader, format).unwrap();
+            let second = deserialize_index(&mut reader, format).unwrap();
+            assert_eq!(sets(first.as_ref()), sets(&positions));
+            assert_eq!(sets(second.as_ref()), sets(&names));
+            assert!(reader.is_empty());
+            assert!(deserialize_index(&mut reader, format).is_err());
+        }
+    }
+
+    #[test]
+    fn indexes_come_back_as_the_same_kind_with_the_same_seed() {
+        register_ordered_index_codec::<u16>("u16");
+        register_multi_index_codec::<(u8, bool)>("(u8, bool)");
+
+        let mut seeded: Index<u16> = Index::with_hasher(Xxh3BuildHasher::new(7));
+        seeded.insert_entity(&3, 1).unwrap();
+        let mut ordered = OrderedIndex::<u16>::new();
+        ordered.insert_entity(&5, 2).unwrap();
+        ordered.insert_entity(&4, 3).unwrap();
+        let mut multi = MultiIndex::<(u8, bool)>::new();
+        multi.insert_entity(&(1, true), 4).unwrap();
+        multi.insert_entity(&(1, false), 5).unwrap();
+
+        for format in formats() {
+            let restored = deserialize_index(&mut checkpoint(&seeded, format).as_slice(), format)
+                .unwrap()
+                .into_any()
+                .downcast::<Index<u16>>()
+                .unwrap();
+            assert_eq!(restored.hasher().seed(), 7);
+            assert_eq!(restored.get(&3), seeded.get(&3));
+
+            let restored = deserialize_index(&mut checkpoint(&ordered, format).as_slice(), format)
+                .unwrap()
+                .into_any()
+                .downcast::<OrderedIndex<u16>>()
+                .unwrap();
+            assert_eq!(restored.values().collect::<Vec<_>>(), [&4, &5]);
+
+            let restored = deserialize_index(&mut checkpoint(&multi, format).as_slice(), format)
+                .unwrap()
+                .into_any()
+                .downcast::<MultiIndex<(u8, bool)>>()
+                .unwrap();
+            assert_eq!(
+                restored.entities_with_prefix(&(true,)),
+                multi.entities_with_prefix(&(true,))
+            );
+            assert_eq!(sets(restored.as_ref()), sets(&multi));
+        }
+    }
+
+    #[test]
+    fn indexes_a_checkpoint_cant_restore_are_not_written() {
+        register_index_codec::<u32>("u32");
+        let mut sorted: Index<u32, Xxh3BuildHasher, SortedVecSet> =
+            Index::with_hasher(Xxh3BuildHasher::default());
+        sorted.insert_entity(&1, 1).unwrap();
+        let mut truncated: Index<u32, TruncatingBuildHasher> =
+            Index::with_hasher(TruncatingBuildHasher::new(8));
+        truncated.insert_entity(&1, 1).unwrap();
+        // Registered as a plain index only, so not as an ordered one.
+        let mut ordered = OrderedIndex::<u32>::new();
+        ordered.insert_entity(&1, 1).unwrap();
+
+        for format in formats() {
+            for index in [&sorted as &dyn TypeErasedIndex, &truncated] {
+                let error = index.serialize_into(&mut Vec::new(), format).unwrap_err();
+                assert!(
+                    matches!(error, IndexError::Serialization(CodecError::Unsupported(_))),
+                    "{error}"
+                );
+            }
+            let bytes = checkpoint(&ordered, format);
+            let Err(error) = deserialize_index(&mut bytes.as_slice(), format) else {
+                panic!("the checkpoint was deserialized");
+            };
+            assert!(matches!(error, CodecError::Unsupported(_)), "{error}");
+        }
+    }
+
+    #[cfg(feature = "serde-json")]
+    #[test]
+    fn json_checkpoints_are_readable() {
+        register_index_codec::<Position>("tests::Position");
+        let mut index = Index::<Position>::new();
+        index.insert_entity(&Position(1, 2), 9).unwrap();
+        index.insert_entity(&Position(1, 2), 7).unwrap();
+        assert_eq!(
+            String::from_utf8(checkpoint(&index, IndexFormat::Json)).unwrap(),
+            r#"{"type":"tests::Position","kind":"Index","seed":0,"entries":[[[1,2],[7,9]]]}"#
+        );
+    }
+
+    #[test]
+    fn unregistered_types_are_errors() {
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        struct Unregistered;
+
+        let mut index = Index::<Unregistered>::new();
+        index.insert_entity(&Unregistered, 1).unwrap();
+        for format in formats() {
+            let error = index.serialize_into(&mut Vec::new(), format).unwrap_err();
+            let IndexError::Serialization(CodecError::UnregisteredType(name)) = error else {
+                panic!("expected an unregistered type, got {error}");
+            };
+            assert!(name.ends_with("Unregistered"), "{name}");
+        }
+
+        #[cfg(feature = "serde-json")]
+        {
+            let checkpoint = r#"{"type":"tests::Nope","kind":"Index","seed":0,"entries":[]}"#;
+            let Err(error) = deserialize_index(&mut checkpoint.as_bytes(), IndexFormat::Json)
+            else {
+                panic!("the checkpoint was deserialized");
+            };
+            assert!(matches!(error, CodecError::UnknownTypeName(name) if name == "tests::Nope"));
+        }
+    }
+
+    #[cfg(feature = "serde-json")]
+    #[test]
+    fn duplicate_values_are_invalid() {
+        register_index_codec::<Position>("tests::Position");
+        let checkpoint = r#"{"type":"tests::Position","kind":"Index","seed":0,"entries":[[[1,2],[1]],[[1,2],[2]]]}"#;
+        let Err(error) = deserialize_index(&mut checkpoint.as_bytes(), IndexFormat::Json) else {
+            panic!("the checkpoint was deserialized");
+        };
+        assert!(error.to_string().contains("appears twice"), "{error}");
+    }
+
+    #[cfg(feature = "serde-json")]
+    #[test]
+    fn values_without_entities_are_invalid() {
+        register_index_codec::<Position>("tests::Position");
+        register_ordered_index_codec::<u16>("u16");
+        register_multi_index_codec::<(u8, bool)>("(u8, bool)");
+        for checkpoint in [
+            r#"{"type":"tests::Position","kind":"Index","seed":0,"entries":[[[1,2],[]]]}"#,
+            r#"{"type":"u16","kind":"OrderedIndex","seed":0,"entries":[[3,[]]]}"#,
+            r#"{"type":"(u8, bool)","kind":"MultiIndex","seed":0,"entries":[[[3,true],[]]]}"#,
+        ] {
+            let Err(error) = deserialize_index(&mut checkpoint.as_bytes(), IndexFormat::Json)
+            else {
+                panic!("{checkpoint} was deserialized");
+            };
+            assert!(error.to_string().contains("has no entities"), "{error}");
+        }
+    }
+
+    #[cfg(feature = "serde-json")]
+    #[test]
+    fn malformed_headers_are_invalid() {
+        register_index_codec::<Position>("tests::Position");
+        for checkpoint in [
+            r#"{"type":7,"kind":"Index","seed":0,"entries":[]}"#,
+            r#"{"type":"tests::Position","kind":null,"seed":0,"entries":[]}"#,
+        ] {
+            let Err(error) = deserialize_index(&mut checkpoint.as_bytes(), IndexFormat::Json)
+            else {
+                panic!("{checkpoint} was deserialized");
+            };
+            assert!(matches!(error, CodecError::Invalid(_)), "{error}");
+            assert!(error.to_string().contains("must be a string"), "{error}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered for another type")]
+    fn names_are_unique() {
+        #[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        struct Impostor;
+
+        register_index_codec::<Position>("tests::Position");
+        register_index_codec::<Impostor>("tests::Position");
+    }
+}
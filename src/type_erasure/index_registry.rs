@@ -0,0 +1,176 @@
+/*!
+
+# A Registry of Indexes by Value Type
+
+A program that indexes entities by several properties ends up with one [`Index<T>`] per property
+type, and every part of it that touches an index has to know where that index lives. An
+[`IndexRegistry`] keeps them all in one place, keyed by the `TypeId` of the value type, and creates
+each `Index<T>` the first time it's asked for one.
+
+Code that knows `T` gets the typed API with [`IndexRegistry::index_mut`], and code that doesn't,
+like a query planner holding a `TypeId` or a report over every index, gets the type-erased API
+with [`IndexRegistry::by_type_id`] and [`IndexRegistry::iter`].
+
+Going from the stored `BxIndex` back to `Index<T>` is a downcast, and the downcast can't fail
+because the key is the value's `TypeId`: the registry only ever stores an `Index<T>` under
+`TypeId::of::<T>()`.
+
+*/
+
+use std::{
+    any::{Any, TypeId},
+    hash::Hash,
+};
+
+use super::type_erased_api::{BxIndex, Index, TypeErasedIndex};
+use crate::hashing::DeterministicHashMap;
+
+/// One [`Index<T>`] per value type `T`, created on first access.
+#[derive(Default)]
+pub struct IndexRegistry {
+    /// In the order the indexes were created, so reports come out in a stable order.
+    indexes: DeterministicHashMap<TypeId, BxIndex>,
+}
+
+impl IndexRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The index of `T` values, if one has been created. An index that hasn't been created yet would be empty, so
+    /// this never needs to create one.
+    pub fn index<T: Hash + Eq + Clone + Any>(&self) -> Option<&Index<T>> {
+        let index = self.indexes.get(&TypeId::of::<T>())?;
+        Some(
+            index
+                .downcast_ref()
+                .expect("the registry only stores an `Index<T>` under the `TypeId` of `T`"),
+        )
+    }
+
+    /// The index of `T` values, created empty if this is the first time it's asked for.
+    pub fn index_mut<T: Hash + Eq + Clone + Any>(&mut self) -> &mut Index<T> {
+        self.indexes
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Index::<T>::new()))
+            .downcast_mut()
+            .expect("the registry only stores an `Index<T>` under the `TypeId` of `T`")
+    }
+
+    /// The index of values whose type has the given `TypeId`, if one has been created.
+    pub fn by_type_id(&self, type_id: TypeId) -> Option<&dyn TypeErasedIndex> {
+        self.indexes.get(&type_id).map(|index| index.as_ref())
+    }
+
+    /// The index of values whose type has the given `TypeId`, if one has been created.
+    pub fn by_type_id_mut(&mut self, type_id: TypeId) -> Option<&mut dyn TypeErasedIndex> {
+        self.indexes
+            .get_mut(&type_id)
+            .map(|index| index.as_mut() as &mut dyn TypeErasedIndex)
+    }
+
+    /// Every index with the `TypeId` of its value type, in the order they were created.
+    pub fn iter(&self) -> impl Iterator<Item = (TypeId, &dyn TypeErasedIndex)> {
+        self.indexes
+            .iter()
+            .map(|(type_id, index)| (*type_id, index.as_ref()))
+    }
+
+    /// The number of indexes that have been created.
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::FastHashSet;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Color(&'static str);
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Age(u8);
+
+    #[test]
+    fn indexes_are_created_on_first_access() {
+        let mut registry = IndexRegistry::new();
+        assert!(registry.index::<Color>().is_none());
+        assert!(registry.is_empty());
+
+        registry
+            .index_mut::<Color>()
+            .insert_entity(&Color("red"), 1)
+            .unwrap();
+        registry
+            .index_mut::<Age>()
+            .insert_entity(&Age(30), 1)
+            .unwrap();
+        registry
+            .index_mut::<Color>()
+            .insert_entity(&Color("red"), 2)
+            .unwrap();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(
+            registry.index::<Color>().unwrap().get(&Color("red")),
+            Some(&FastHashSet::from_iter([1, 2]))
+        );
+    }
+
+    #[test]
+    fn erased_access_reaches_the_same_index() {
+        let mut registry = IndexRegistry::new();
+        registry
+            .index_mut::<Age>()
+            .insert_entity(&Age(30), 1)
+            .unwrap();
+        let thirty = registry.index::<Age>().unwrap().hash_value(&Age(30));
+
+        let ages = registry.by_type_id_mut(TypeId::of::<Age>()).unwrap();
+        assert!(ages.insert_entity_with_hash(thirty, 2).unwrap());
+        assert!(registry.by_type_id(TypeId::of::<Color>()).is_none());
+        assert_eq!(
+            registry
+                .index::<Age>()
+                .unwrap()
+                .get(&Age(30))
+                .map(|set| set.len()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn iteration_reports_every_index_in_creation_order() {
+        let mut registry = IndexRegistry::new();
+        registry
+            .index_mut::<Age>()
+            .insert_entity(&Age(30), 1)
+            .unwrap();
+        registry.index_mut::<Color>();
+        registry
+            .index_mut::<Age>()
+            .insert_entity(&Age(31), 2)
+            .unwrap();
+
+        let report: Vec<(TypeId, usize)> = registry
+            .iter()
+            .map(|(type_id, index)| (type_id, index.iter_hashes().count()))
+            .collect();
+        assert_eq!(
+            report,
+            [(TypeId::of::<Age>(), 2), (TypeId::of::<Color>(), 0)]
+        );
+    }
+
+    #[test]
+    fn as_any_only_downcasts_to_the_concrete_type() {
+        let index: BxIndex = Box::new(Index::<Age>::new());
+        assert!(index.as_any().downcast_ref::<Index<Age>>().is_some());
+        assert!(index.as_any().downcast_ref::<Index<Color>>().is_none());
+    }
+}
@@ -0,0 +1,191 @@
+/*!
+
+# Index Statistics
+
+To choose the cheapest index for a query, a planner needs numbers: how many values an index has,
+how many entities, and how big their sets are.
+[`TypeErasedIndex::stats`](super::type_erased_api::TypeErasedIndex::stats) reports an
+[`IndexStats`] for any index, whatever its value type.
+
+An [`Index<T>`](super::type_erased_api::Index) keeps its statistics up to date as it changes,
+rather than walking its sets on every call. It counts its sets by size, so the largest and median
+set sizes come from a table with one entry per distinct _size_, which stays small however many
+values the index has. It also keeps the sum of the sets' [`heap_bytes`](EntitySet::heap_bytes),
+so the memory it reports follows the layout of whichever [`EntitySet`] it uses.
+
+The one change an index can't see as it happens is one made through a `&mut` set handed out by
+`get_mut` and its relatives. The index remembers the last set it handed out, and its size at the
+time, and catches up with it before the next change or in `stats`.
+
+*/
+
+use std::collections::BTreeMap;
+
+use super::entity_set::EntitySet;
+
+/// A summary of an index's size, as returned by
+/// [`TypeErasedIndex::stats`](super::type_erased_api::TypeErasedIndex::stats).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    /// The number of distinct values.
+    pub distinct_values: usize,
+    /// The sum of the sizes of the sets. An entity with several values is counted once per value.
+    pub total_entities: usize,
+    /// The size of the largest set, 0 for an empty index.
+    pub largest_set: usize,
+    /// The median set size. With an even number of sets, the smaller of the two middle sizes.
+    pub median_set: usize,
+    /// How much heap memory the index uses, in bytes: its tables plus the [`heap_bytes`](EntitySet::heap_bytes) of
+    /// every set. Memory owned by the values themselves isn't counted.
+    pub heap_bytes: usize,
+    /// The number of buckets in the index's `HashTable`, or 0 if it doesn't have one.
+    pub buckets: usize,
+}
+
+/// What the counters know about one set: its length and its heap memory.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SetSize {
+    len: usize,
+    heap_bytes: usize,
+}
+
+impl SetSize {
+    pub(crate) fn of<E: EntitySet + ?Sized>(set: &E) -> Self {
+        Self {
+            len: set.len(),
+            heap_bytes: set.heap_bytes(),
+        }
+    }
+}
+
+/// How many sets of each size an index has.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SetSizes {
+    /// The number of sets of each length.
+    counts: BTreeMap<usize, usize>,
+    sets: usize,
+    entities: usize,
+    heap_bytes: usize,
+}
+
+impl SetSizes {
+    pub(crate) fn add(&mut self, size: SetSize) {
+        *self.counts.entry(size.len).or_default() += 1;
+        self.sets += 1;
+        self.entities += size.len;
+        self.heap_bytes += size.heap_bytes;
+    }
+
+    pub(crate) fn remove(&mut self, size: SetSize) {
+        let count = self
+            .counts
+            .get_mut(&size.len)
+            .expect("removed a set size that was never added");
+        *count -= 1;
+        if *count == 0 {
+            self.counts.remove(&size.len);
+        }
+        self.sets -= 1;
+        self.entities -= size.len;
+        self.heap_bytes -= size.heap_bytes;
+    }
+
+    /// A set changed size from `from` to `to`.
+    pub(crate) fn resize(&mut self, from: SetSize, to: SetSize) {
+        if from != to {
+            self.remove(from);
+            self.add(to);
+        }
+    }
+
+    pub(crate) fn largest(&self) -> usize {
+        self.counts.last_key_value().map_or(0, |(size, _)| *size)
+    }
+
+    /// See [`IndexStats::median_set`].
+    pub(crate) fn median(&self) -> usize {
+        let Some(middle) = self.sets.checked_sub(1).map(|last| last / 2) else {
+            return 0;
+        };
+        let mut seen = 0;
+        for (size, count) in &self.counts {
+            seen += count;
+            if seen > middle {
+                return *size;
+            }
+        }
+        unreachable!("the counts add up to the number of sets")
+    }
+
+    /// The statistics of an index with these sets, given the heap memory of its own tables and its number of buckets.
+    pub(crate) fn stats(&self, table_bytes: usize, buckets: usize) -> IndexStats {
+        IndexStats {
+            distinct_values: self.sets,
+            total_entities: self.entities,
+            largest_set: self.largest(),
+            median_set: self.median(),
+            heap_bytes: table_bytes + self.heap_bytes,
+            buckets,
+        }
+    }
+}
+
+impl FromIterator<SetSize> for SetSizes {
+    fn from_iter<I: IntoIterator<Item = SetSize>>(sizes: I) -> Self {
+        let mut set_sizes = Self::default();
+        for size in sizes {
+            set_sizes.add(size);
+        }
+        set_sizes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sets of the given lengths, each with 8 bytes of heap per entity.
+    fn sizes(lens: impl IntoIterator<Item = usize>) -> SetSizes {
+        lens.into_iter().map(size).collect()
+    }
+
+    fn size(len: usize) -> SetSize {
+        SetSize {
+            len,
+            heap_bytes: 8 * len,
+        }
+    }
+
+    #[test]
+    fn medians_take_the_smaller_middle_size() {
+        assert_eq!(SetSizes::default().median(), 0);
+        assert_eq!(sizes([4]).median(), 4);
+        assert_eq!(sizes([9, 1, 4]).median(), 4);
+        assert_eq!(sizes([9, 1, 4, 6]).median(), 4);
+        assert_eq!(sizes([2, 2, 2, 7]).median(), 2);
+    }
+
+    #[test]
+    fn resizing_keeps_every_count_in_step() {
+        let mut set_sizes = sizes([1, 3, 3]);
+        set_sizes.resize(size(3), size(8));
+        set_sizes.resize(size(1), size(1));
+        set_sizes.remove(size(3));
+        set_sizes.add(size(0));
+        let stats = set_sizes.stats(100, 0);
+        assert_eq!(
+            (
+                stats.distinct_values,
+                stats.total_entities,
+                stats.largest_set,
+                stats.median_set,
+                stats.heap_bytes
+            ),
+            (3, 9, 8, 1, 100 + 72)
+        );
+
+        set_sizes.remove(size(8));
+        assert_eq!(set_sizes.largest(), 1);
+        assert_eq!(set_sizes.stats(0, 0), sizes([0, 1]).stats(0, 0));
+    }
+}
@@ -4,6 +4,14 @@
 
 The [type-erased API](type_erased_api/index.html) module is an illustration
 of a type, a database index, having both a typed and type-erased API.
+Each value's entities are stored in an [`entity_set`](entity_set/index.html) of your choosing.
+The [`index_codec`](index_codec/index.html) module, behind the `serde-json` and `serde-postcard`
+features, serializes those indexes through the type-erased API, and
+[`multi_index`](multi_index/index.html) keys them by tuples of properties. The
+[`ordered_index`](ordered_index/index.html) module keeps values in order, for range queries, and
+[`query`](query/index.html) combines the sets of several indexes without knowing their types.
+An [`index_registry`](index_registry/index.html) keeps one index per value type, and
+[`index_stats`](index_stats/index.html) reports how big each one is.
 
 Sometimes you don't have complete control over the type you want to expose.
 Suppose you want a type-erased interface to a _type_ but not _instances_
@@ -13,5 +21,20 @@ module illustrates how to do this in a couple of different ways.
 
 */
 
+#[cfg(feature = "std")]
+pub mod entity_set;
+#[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+pub mod index_codec;
+#[cfg(feature = "std")]
+pub mod index_registry;
+#[cfg(feature = "std")]
+pub mod index_stats;
+#[cfg(feature = "std")]
+pub mod multi_index;
+#[cfg(feature = "std")]
+pub mod ordered_index;
+#[cfg(feature = "std")]
+pub mod query;
 pub mod static_interface;
+#[cfg(feature = "std")]
 pub mod type_erased_api;
@@ -0,0 +1,632 @@
+/*!
+
+# Multi-Property Indexes
+
+Indexing entities on a combination of properties, say `(Age, County)`, with a plain
+[`Index`] means keying it by a tuple. That works, but a tuple's hash depends on the order of its
+fields: `(Age, County)` and `(County, Age)` are different keys with different hashes, so two parts of
+a program that build the "same" key in a different order never meet.
+
+A [`MultiIndex<K>`] is keyed by a tuple of up to eight properties and hashes it in a _canonical
+order_: components are sorted by their [`KeyComponent::COMPONENT_NAME`], so `(Age(30),
+County("Kent"))` and `(County("Kent"), Age(30))` have the same hash and find the same set. The name
+is declared rather than taken from `std::any::type_name`, whose output may change between compiler
+versions, so the order and the hashes are the same in every build. [`key_component_impl!`] names a
+type after its path. Components of the same type keep their relative order, since the index can't
+tell which is which. Give each property its own newtype and this never comes up.
+
+Every proper prefix of a key in canonical order is indexed as well, so the index can answer
+_prefix queries_: all entities whose key starts with the given components. With the
+components above, `Age` sorts before `County`, so `(Age(30),)` finds every thirty-year-old in any
+county. Through the type-erased API, [`TypeErasedMultiIndex`] takes the hash of the prefix, computed
+with [`MultiIndex::hash_value`] on a shorter tuple.
+
+The index is an [`Index`] of [`CanonicalKey<K>`]s underneath, and implements [`TypeErasedIndex`]
+by deferring to it.
+
+*/
+
+use std::{
+    any::{Any, TypeId, type_name},
+    hash::{Hash, Hasher},
+};
+
+#[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+use super::index_codec::{IndexFormat, IndexKind};
+use super::{
+    entity_set::EntitySet,
+    index_stats::IndexStats,
+    type_erased_api::{EntityId, HashValue, Index, IndexError, TypeErasedIndex},
+};
+use crate::hashing::{BuildHasher128, FastHashMap, FastHashSet, Fingerprint, Xxh3BuildHasher};
+
+/// A property that can be a component of a [`MultiKey`].
+pub trait KeyComponent: Hash + Eq + Clone + Any {
+    /// The name that puts the component in canonical order and is hashed before it. It must be unique to the type and
+    /// must not change, or keys hashed by another build won't be found.
+    const COMPONENT_NAME: &'static str;
+}
+
+/// Implements [`KeyComponent`] for each of the given types, naming it after its path.
+#[macro_export]
+macro_rules! key_component_impl {
+    ($($type_name:ident),+ $(,)?) => {
+        $(
+            impl $crate::type_erasure::multi_index::KeyComponent for $type_name {
+                const COMPONENT_NAME: &'static str = concat!(module_path!(), "::", stringify!($type_name));
+            }
+        )+
+    };
+}
+
+macro_rules! impl_primitive_key_component {
+    ($($type:ty),+) => {
+        $(
+            impl KeyComponent for $type {
+                const COMPONENT_NAME: &'static str = stringify!($type);
+            }
+        )+
+    };
+}
+
+impl_primitive_key_component!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    bool,
+    char,
+    String,
+    &'static str
+);
+
+/// A tuple of one to eight properties that a [`MultiIndex`] can be keyed by.
+pub trait MultiKey: Hash + Eq + Clone + Any {
+    /// The number of components.
+    const ARITY: usize;
+
+    /// The names of the components, in canonical order.
+    fn canonical_component_names() -> Vec<&'static str>;
+
+    /// Writes the first `len` components in canonical order to `state`, each preceded by its name.
+    fn hash_canonical_prefix<H: Hasher>(&self, len: usize, state: &mut H);
+}
+
+/// The positions of the components sorted by name. The sort is stable, so components of the same type keep their
+/// order.
+///
+/// # Panics
+///
+/// If two different component types share a name, since their order, and so the key's hash, would depend on the order
+/// they were written in.
+fn canonical_order<const N: usize>(components: &[(&'static str, TypeId); N]) -> [usize; N] {
+    let mut order: [usize; N] = std::array::from_fn(|position| position);
+    order.sort_by_key(|&position| components[position].0);
+    for pair in order.windows(2) {
+        let (first, second) = (components[pair[0]], components[pair[1]]);
+        assert!(
+            first.0 != second.0 || first.1 == second.1,
+            "two key component types are both named `{}`",
+            first.0
+        );
+    }
+    order
+}
+
+macro_rules! impl_multi_key {
+    ($arity:literal; $($component:ident . $position:tt),+) => {
+        impl<$($component: KeyComponent),+> MultiKey for ($($component,)+) {
+            const ARITY: usize = $arity;
+
+            fn canonical_component_names() -> Vec<&'static str> {
+                let components = [$(($component::COMPONENT_NAME, TypeId::of::<$component>())),+];
+                canonical_order(&components)
+                    .map(|position| components[position].0)
+                    .to_vec()
+            }
+
+            fn hash_canonical_prefix<__H: Hasher>(&self, len: usize, state: &mut __H) {
+                let components = [$(($component::COMPONENT_NAME, TypeId::of::<$component>())),+];
+                for position in canonical_order(&components).into_iter().take(len) {
+                    match position {
+                        $($position => {
+                            $component::COMPONENT_NAME.hash(state);
+                            self.$position.hash(state);
+                        })+
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl_multi_key!(1; A.0);
+impl_multi_key!(2; A.0, B.1);
+impl_multi_key!(3; A.0, B.1, C.2);
+impl_multi_key!(4; A.0, B.1, C.2, D.3);
+impl_multi_key!(5; A.0, B.1, C.2, D.3, E.4);
+impl_multi_key!(6; A.0, B.1, C.2, D.3, E.4, F.5);
+impl_multi_key!(7; A.0, B.1, C.2, D.3, E.4, F.5, G.6);
+impl_multi_key!(8; A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7);
+
+/// A key whose `Hash` impl writes its components in canonical order, which is what a [`MultiIndex`] stores.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanonicalKey<K>(pub K);
+
+impl<K: MultiKey> Hash for CanonicalKey<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash_canonical_prefix(K::ARITY, state);
+    }
+}
+
+/// Serializes as `K`, so a `MultiIndex` can be checkpointed once `K` is registered with
+/// [`register_multi_index_codec`](super::index_codec::register_multi_index_codec).
+#[cfg(feature = "serde")]
+impl<K: serde::Serialize> serde::Serialize for CanonicalKey<K> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K: serde::Deserialize<'de>> serde::Deserialize<'de> for CanonicalKey<K> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        K::deserialize(deserializer).map(CanonicalKey)
+    }
+}
+
+/// The first `len` components of `key` in canonical order, for hashing.
+struct Prefix<'a, K> {
+    key: &'a K,
+    len: usize,
+}
+
+impl<K: MultiKey> Hash for Prefix<'_, K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash_canonical_prefix(self.len, state);
+    }
+}
+
+/// Reports a collision between keys of type `K` rather than the `CanonicalKey<K>`s the inner index stores.
+fn uncanonical_error<K: MultiKey>(error: IndexError) -> IndexError {
+    let uncanonical = |value: Box<dyn Any>| -> Box<dyn Any> {
+        match value.downcast::<CanonicalKey<K>>() {
+            Ok(key) => Box::new(key.0),
+            Err(value) => value,
+        }
+    };
+    match error {
+        IndexError::Collision {
+            hash,
+            stored,
+            incoming,
+            ..
+        } => IndexError::Collision {
+            hash,
+            value_type: type_name::<K>(),
+            stored: uncanonical(stored),
+            incoming: uncanonical(incoming),
+        },
+        error => error,
+    }
+}
+
+/// An index keyed by a tuple of properties, hashed in canonical order, that answers prefix queries.
+pub struct MultiIndex<K: MultiKey, S: BuildHasher128 = Xxh3BuildHasher> {
+    index: Index<CanonicalKey<K>, S>,
+    /// The full keys that start with each proper prefix.
+    keys_by_prefix: FastHashMap<HashValue, FastHashSet<HashValue>>,
+    /// The proper prefixes of each full key, so that they can be forgotten when the key is removed by hash.
+    prefixes_by_key: FastHashMap<HashValue, Vec<HashValue>>,
+}
+
+impl<K: MultiKey> MultiIndex<K> {
+    pub fn new() -> Self {
+        Self::with_hasher(Xxh3BuildHasher::default())
+    }
+}
+
+impl<K: MultiKey> Default for MultiIndex<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Contains the typed API
+impl<K: MultiKey, S: BuildHasher128 + 'static> MultiIndex<K, S> {
+    pub fn with_hasher(build_hasher: S) -> Self {
+        Self {
+            index: Index::with_hasher(build_hasher),
+            keys_by_prefix: FastHashMap::default(),
+            prefixes_by_key: FastHashMap::default(),
+        }
+    }
+
+    /// The hash of `key` in canonical order. `key` can be the index's key type in any order, or a prefix of it, which
+    /// is the hash [`TypeErasedMultiIndex::entities_with_prefix_hash`] expects.
+    pub fn hash_value<Q: MultiKey>(&self, key: &Q) -> HashValue {
+        self.prefix_hash(key, Q::ARITY)
+    }
+
+    fn prefix_hash<Q: MultiKey>(&self, key: &Q, len: usize) -> HashValue {
+        Fingerprint::new(self.index.hasher().hash_one_128(&Prefix { key, len }))
+    }
+
+    /// Inserts an entity into the set associated with `key`, creating a new set if one does not yet exist. Returns
+    /// whether the `entity_id` was newly inserted.
+    ///
+    /// Fails only when [`AUDIT_COLLISIONS`](super::type_erased_api::AUDIT_COLLISIONS) is on and a different value
+    /// with the same hash is already stored.
+    pub fn insert_entity(&mut self, key: &K, entity_id: EntityId) -> Result<bool, IndexError> {
+        let inserted = self
+            .index
+            .insert_entity(&CanonicalKey(key.clone()), entity_id)
+            .map_err(uncanonical_error::<K>)?;
+        self.remember_prefixes(key);
+        Ok(inserted)
+    }
+
+    /// The set associated with `key`, which may be the index's key type in any order.
+    pub fn get<Q: MultiKey>(&self, key: &Q) -> Option<&FastHashSet<EntityId>> {
+        self.index.set_with_hash(self.hash_value(key))
+    }
+
+    /// All entities whose key starts with `prefix` in canonical order. A full key finds just its own set.
+    pub fn entities_with_prefix<Q: MultiKey>(&self, prefix: &Q) -> FastHashSet<EntityId> {
+        self.entities_with_prefix_hash(self.hash_value(prefix))
+    }
+
+    /// Removes `entity_id` from the set associated with `key`, removing the key if its set becomes empty. Returns
+    /// whether the entity was in the set.
+    pub fn remove_entity(&mut self, key: &K, entity_id: EntityId) -> bool {
+        let hash = self.hash_value(key);
+        self.remove_entity_with_hash(hash, entity_id)
+    }
+
+    /// Removes `key` and its set from the index, returning the set if the key was present.
+    pub fn remove_value(&mut self, key: &K) -> Option<FastHashSet<EntityId>> {
+        let set = self.index.remove_value(&CanonicalKey(key.clone()));
+        self.forget_prefixes_if_removed(self.hash_value(key));
+        set
+    }
+
+    /// Moves `entity_id` from the set associated with `from` to the set associated with `to`, creating and removing
+    /// sets as needed. See [`Index::move_entity`].
+    pub fn move_entity(
+        &mut self,
+        from: &K,
+        to: &K,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        let moved = self
+            .index
+            .move_entity(
+                &CanonicalKey(from.clone()),
+                &CanonicalKey(to.clone()),
+                entity_id,
+            )
+            .map_err(uncanonical_error::<K>)?;
+        self.remember_prefixes(to);
+        self.forget_prefixes_if_removed(self.hash_value(from));
+        Ok(moved)
+    }
+
+    /// Iterates over the keys in the index with their sets, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &FastHashSet<EntityId>)> {
+        self.index.iter().map(|(key, set)| (&key.0, set))
+    }
+
+    /// The number of distinct keys in the index.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    fn remember_prefixes(&mut self, key: &K) {
+        let hash = self.hash_value(key);
+        if self.prefixes_by_key.contains_key(&hash) {
+            return;
+        }
+        let prefixes: Vec<HashValue> = (1..K::ARITY)
+            .map(|len| self.prefix_hash(key, len))
+            .collect();
+        for prefix in &prefixes {
+            self.keys_by_prefix.entry(*prefix).or_default().insert(hash);
+        }
+        self.prefixes_by_key.insert(hash, prefixes);
+    }
+
+    fn forget_prefixes_if_removed(&mut self, hash: HashValue) {
+        if self.index.has_hash(hash) {
+            return;
+        }
+        for prefix in self.prefixes_by_key.remove(&hash).unwrap_or_default() {
+            if let Some(keys) = self.keys_by_prefix.get_mut(&prefix) {
+                keys.remove(&hash);
+                if keys.is_empty() {
+                    self.keys_by_prefix.remove(&prefix);
+                }
+            }
+        }
+    }
+}
+
+/// The type-erased API of a [`MultiIndex`]: a [`TypeErasedIndex`] that also answers prefix queries.
+pub trait TypeErasedMultiIndex: TypeErasedIndex {
+    /// The number of components in a full key.
+    fn arity(&self) -> usize;
+
+    /// All entities whose key starts with the prefix with the given hash. The hash of a full key finds just its own
+    /// set.
+    fn entities_with_prefix_hash(&self, prefix: HashValue) -> FastHashSet<EntityId>;
+}
+
+/// A "boxed" `TypeErasedMultiIndex`
+pub type BxMultiIndex = Box<dyn TypeErasedMultiIndex>;
+
+impl<K: MultiKey, S: BuildHasher128 + 'static> TypeErasedMultiIndex for MultiIndex<K, S> {
+    fn arity(&self) -> usize {
+        K::ARITY
+    }
+
+    fn entities_with_prefix_hash(&self, prefix: HashValue) -> FastHashSet<EntityId> {
+        if let Some(set) = self.index.set_with_hash(prefix) {
+            return set.clone();
+        }
+        self.keys_by_prefix
+            .get(&prefix)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| self.index.set_with_hash(*key))
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+/// Defers to the underlying [`Index`], keeping track of removed keys.
+impl<K: MultiKey, S: BuildHasher128 + 'static> TypeErasedIndex for MultiIndex<K, S> {
+    fn insert_entity_with_hash(
+        &mut self,
+        hash: HashValue,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        self.index.insert_entity_with_hash(hash, entity_id)
+    }
+
+    fn get_with_hash(&self, hash: HashValue) -> Option<&dyn EntitySet> {
+        self.index.get_with_hash(hash)
+    }
+
+    fn get_with_hash_mut(&mut self, hash: HashValue) -> Option<&mut dyn EntitySet> {
+        self.index.get_with_hash_mut(hash)
+    }
+
+    fn has_hash(&self, hash: HashValue) -> bool {
+        self.index.has_hash(hash)
+    }
+
+    fn remove_entity_with_hash(&mut self, hash: HashValue, entity_id: EntityId) -> bool {
+        let removed = self.index.remove_entity_with_hash(hash, entity_id);
+        self.forget_prefixes_if_removed(hash);
+        removed
+    }
+
+    fn move_entity_between_hashes(
+        &mut self,
+        from: HashValue,
+        to: HashValue,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        let moved = self.index.move_entity_between_hashes(from, to, entity_id)?;
+        self.forget_prefixes_if_removed(from);
+        Ok(moved)
+    }
+
+    fn iter_hashes(&self) -> Box<dyn Iterator<Item = (HashValue, &dyn EntitySet)> + '_> {
+        self.index.iter_hashes()
+    }
+
+    fn for_each_hash(&self, f: &mut dyn FnMut(HashValue, &dyn EntitySet)) {
+        self.index.for_each_hash(f);
+    }
+
+    #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+    fn serialize_into(
+        &self,
+        writer: &mut dyn std::io::Write,
+        format: IndexFormat,
+    ) -> Result<(), IndexError> {
+        self.index.serialize_as(IndexKind::Multi, writer, format)
+    }
+
+    /// The inner index's statistics, plus the memory of the prefix tables.
+    fn stats(&self) -> IndexStats {
+        let mut stats = self.index.stats();
+        stats.heap_bytes +=
+            self.keys_by_prefix.allocation_size() + self.prefixes_by_key.allocation_size();
+        stats
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        type_name::<K>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Age(u8);
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct County(&'static str);
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Vaccinated(bool);
+
+    crate::key_component_impl!(Age, County, Vaccinated);
+
+    mod elsewhere {
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub struct Age(pub u8);
+
+        impl crate::type_erasure::multi_index::KeyComponent for Age {
+            const COMPONENT_NAME: &'static str =
+                <super::Age as super::KeyComponent>::COMPONENT_NAME;
+        }
+    }
+
+    fn set(entity_ids: impl IntoIterator<Item = EntityId>) -> FastHashSet<EntityId> {
+        entity_ids.into_iter().collect()
+    }
+
+    #[test]
+    fn component_order_does_not_matter() {
+        let mut index = MultiIndex::<(Age, County)>::new();
+        index.insert_entity(&(Age(30), County("Kent")), 1).unwrap();
+        index.insert_entity(&(Age(30), County("Kent")), 2).unwrap();
+
+        let swapped = (County("Kent"), Age(30));
+        assert_eq!(
+            index.hash_value(&swapped),
+            index.hash_value(&(Age(30), County("Kent")))
+        );
+        assert_eq!(index.get(&swapped), Some(&set([1, 2])));
+        assert_eq!(index.get(&(County("Kent"), Age(31))), None);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn components_of_the_same_type_keep_their_order() {
+        let mut index = MultiIndex::<(u8, u8)>::new();
+        index.insert_entity(&(1, 2), 1).unwrap();
+        assert_eq!(index.get(&(2u8, 1u8)), None);
+        assert_ne!(index.hash_value(&(1u8, 2u8)), index.hash_value(&(2u8, 1u8)));
+    }
+
+    #[test]
+    fn canonical_order_sorts_by_component_name() {
+        assert_eq!(
+            <(Vaccinated, County, Age)>::canonical_component_names(),
+            [
+                Age::COMPONENT_NAME,
+                County::COMPONENT_NAME,
+                Vaccinated::COMPONENT_NAME
+            ]
+        );
+        assert_eq!(County::COMPONENT_NAME, concat!(module_path!(), "::County"));
+        assert_eq!(<(u8, u16, u32, u64, i8, i16, i32, i64)>::ARITY, 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "both named")]
+    fn component_names_must_be_unique() {
+        MultiIndex::<(Age, elsewhere::Age)>::new().hash_value(&(Age(1), elsewhere::Age(2)));
+    }
+
+    #[test]
+    fn prefix_queries_through_the_erased_api() {
+        let mut index = MultiIndex::<(Vaccinated, County, Age)>::new();
+        let people = [
+            (Age(30), County("Kent"), Vaccinated(true)),
+            (Age(30), County("Kent"), Vaccinated(false)),
+            (Age(30), County("Essex"), Vaccinated(true)),
+            (Age(40), County("Kent"), Vaccinated(true)),
+        ];
+        for (entity_id, (age, county, vaccinated)) in people.into_iter().enumerate() {
+            index
+                .insert_entity(&(vaccinated, county, age), entity_id as EntityId)
+                .unwrap();
+        }
+
+        // In canonical order, the key is (Age, County, Vaccinated).
+        let thirty = index.hash_value(&(Age(30),));
+        let thirty_in_kent = index.hash_value(&(County("Kent"), Age(30)));
+        let full = index.hash_value(&(Age(40), Vaccinated(true), County("Kent")));
+        let erased: &dyn TypeErasedMultiIndex = &index;
+        assert_eq!(erased.arity(), 3);
+        assert_eq!(erased.entities_with_prefix_hash(thirty), set([0, 1, 2]));
+        assert_eq!(
+            erased.entities_with_prefix_hash(thirty_in_kent),
+            set([0, 1])
+        );
+        assert_eq!(erased.entities_with_prefix_hash(full), set([3]));
+
+        // `County` alone isn't a prefix in canonical order.
+        assert!(index.entities_with_prefix(&(County("Kent"),)).is_empty());
+    }
+
+    #[cfg(any(debug_assertions, feature = "collision-audit"))]
+    #[test]
+    fn collisions_report_the_keys_as_given() {
+        use crate::hashing::TruncatingBuildHasher;
+
+        // Every key hashes to zero.
+        let mut index = MultiIndex::<(Age, County), _>::with_hasher(TruncatingBuildHasher::new(0));
+        index.insert_entity(&(Age(30), County("Kent")), 1).unwrap();
+        let error = index
+            .insert_entity(&(Age(31), County("Kent")), 2)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            IndexError::Collision { value_type, .. } if value_type == type_name::<(Age, County)>()
+        ));
+        assert_eq!(
+            error.colliding_values::<(Age, County)>(),
+            Some((&(Age(30), County("Kent")), &(Age(31), County("Kent"))))
+        );
+    }
+
+    #[test]
+    fn removing_keys_forgets_their_prefixes() {
+        let mut index = MultiIndex::<(Age, County)>::new();
+        index.insert_entity(&(Age(30), County("Kent")), 1).unwrap();
+        index.insert_entity(&(Age(30), County("Essex")), 2).unwrap();
+        assert_eq!(index.entities_with_prefix(&(Age(30),)), set([1, 2]));
+
+        assert!(
+            index
+                .move_entity(&(Age(30), County("Kent")), &(Age(31), County("Kent")), 1)
+                .unwrap()
+        );
+        assert_eq!(index.entities_with_prefix(&(Age(30),)), set([2]));
+        assert_eq!(index.entities_with_prefix(&(Age(31),)), set([1]));
+
+        let essex = index.hash_value(&(Age(30), County("Essex")));
+        assert!(index.remove_entity_with_hash(essex, 2));
+        assert!(index.entities_with_prefix(&(Age(30),)).is_empty());
+        assert_eq!(
+            index.remove_value(&(Age(31), County("Kent"))),
+            Some(set([1]))
+        );
+        assert!(index.is_empty());
+        assert!(index.keys_by_prefix.is_empty());
+        assert!(index.prefixes_by_key.is_empty());
+    }
+}
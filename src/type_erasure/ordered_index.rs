@@ -0,0 +1,662 @@
+/*!
+
+# Ordered Indexes and Range Queries
+
+An [`Index`](super::type_erased_api::Index) finds a value by its hash, and hashes don't preserve
+order, so it can't answer "everyone aged 18 to 65" or "every event in March" short of looking up
+each value in the range. An [`OrderedIndex<T>`] keeps its values in a `BTreeMap` instead, so
+[`OrderedIndex::range`] walks just the values in the range, in order.
+
+It also keeps the hash of each value, and implements [`TypeErasedIndex`] over those hashes exactly
+like an `Index<T>`, so the two are interchangeable wherever only the type-erased API is used.
+
+Range queries through the type-erased API are the [`TypeErasedRangeIndex`] trait. The catch is the
+bounds: a generic query planner that doesn't know `T` has to describe `lo` and `hi` somehow, and a
+hash can only name a value, not say where an _absent_ value would sort. So a bound is a
+[`RangeKey`], one of
+
+- the hash of a value that's in the index, e.g. one the planner got from
+  [`iter_hashes`](TypeErasedIndex::iter_hashes) or from another index over the same values;
+- the value itself behind `&dyn Any`, built with [`RangeKey::value`], for code that has it but not
+  its static type;
+- the value serialized with [`encode_key`](super::index_codec::encode_key), decoded with the codec
+  registered for `T` (needs the `serde-json` or `serde-postcard` feature). This is the one to use for
+  bounds that come from outside the program, like a query string.
+
+```rust,ignore
+let ages: Box<dyn TypeErasedRangeIndex> = Box::new(ages);
+let lo = encode_key(&Age(18), IndexFormat::Json)?;
+let hi = encode_key(&Age(65), IndexFormat::Json)?;
+let adults = ages.entities_in_range(
+    Bound::Included(RangeKey::Serialized { format: IndexFormat::Json, bytes: &lo }),
+    Bound::Excluded(RangeKey::Serialized { format: IndexFormat::Json, bytes: &hi }),
+)?;
+```
+
+Every value is stored twice, once in the tree and once by its hash, so an `OrderedIndex` is best
+suited to small values like numbers and dates.
+
+*/
+
+use std::{
+    any::{Any, type_name},
+    borrow::Cow,
+    collections::BTreeMap,
+    hash::Hash,
+    ops::{Bound, RangeBounds},
+};
+
+#[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+use super::index_codec::{self, IndexFormat, IndexKind, IndexShape};
+use super::{
+    entity_set::EntitySet,
+    index_stats::{IndexStats, SetSize, SetSizes},
+    type_erased_api::{EntityId, HashValue, IndexError, TypeErasedIndex},
+};
+use crate::hashing::{BuildHasher128, FastHashSet, Fingerprint, FingerprintMap, Xxh3BuildHasher};
+
+/// An index whose values are kept in order, so it can answer range queries.
+///
+/// Like [`Index`](super::type_erased_api::Index), it's generic over the [`BuildHasher128`] that
+/// computes the hashes the type-erased API uses.
+pub struct OrderedIndex<T: Ord + Hash + Clone + Any, S: BuildHasher128 = Xxh3BuildHasher> {
+    /// Each value with its hash and the set of entities associated with it.
+    values: BTreeMap<T, (HashValue, FastHashSet<EntityId>)>,
+    /// The value with each hash, for the type-erased API.
+    by_hash: FingerprintMap<T>,
+    build_hasher: S,
+}
+
+impl<T: Ord + Hash + Clone + Any> OrderedIndex<T> {
+    pub fn new() -> Self {
+        Self::with_hasher(Xxh3BuildHasher::default())
+    }
+}
+
+impl<T: Ord + Hash + Clone + Any> Default for OrderedIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Hash + Clone + Any, S: BuildHasher128> OrderedIndex<T, S> {
+    pub fn with_hasher(build_hasher: S) -> Self {
+        Self {
+            values: BTreeMap::new(),
+            by_hash: FingerprintMap::default(),
+            build_hasher,
+        }
+    }
+
+    /// The hasher the index computes its hashes with.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// The hash of `key` as computed by this index. This is the hash the type-erased API expects.
+    pub fn hash_value(&self, key: &T) -> HashValue {
+        Fingerprint::new(self.build_hasher.hash_one_128(key))
+    }
+
+    /// Inserts an entity into the set associated with `key`, creating a new set if one does not yet exist. Returns
+    /// whether the entity was newly inserted.
+    ///
+    /// Values are told apart with `Ord`, not by their hashes, so unequal values with the same hash can't be merged.
+    /// They would make the type-erased API ambiguous, though, so inserting one fails whether or not
+    /// [`AUDIT_COLLISIONS`](super::type_erased_api::AUDIT_COLLISIONS) is on.
+    pub fn insert_entity(&mut self, key: &T, entity_id: EntityId) -> Result<bool, IndexError> {
+        if let Some((_, set)) = self.values.get_mut(key) {
+            return Ok(set.insert(entity_id));
+        }
+        self.insert_value(key.clone(), FastHashSet::from_iter([entity_id]))?;
+        Ok(true)
+    }
+
+    /// Inserts `key` with the given set, replacing its set if the value is already in the index.
+    ///
+    /// Fails if a different value with the same hash is already stored.
+    pub fn insert_value(&mut self, key: T, set: FastHashSet<EntityId>) -> Result<(), IndexError> {
+        if let Some((_, existing)) = self.values.get_mut(&key) {
+            *existing = set;
+            return Ok(());
+        }
+        let hash = self.hash_value(&key);
+        if let Some(stored) = self.by_hash.get(&hash) {
+            return Err(IndexError::Collision {
+                hash,
+                value_type: type_name::<T>(),
+                stored: Box::new(stored.clone()),
+                incoming: Box::new(key),
+            });
+        }
+        self.by_hash.insert(hash, key.clone());
+        self.values.insert(key, (hash, set));
+        Ok(())
+    }
+
+    pub fn get(&self, key: &T) -> Option<&FastHashSet<EntityId>> {
+        self.values.get(key).map(|(_, set)| set)
+    }
+
+    pub fn get_mut(&mut self, key: &T) -> Option<&mut FastHashSet<EntityId>> {
+        self.values.get_mut(key).map(|(_, set)| set)
+    }
+
+    /// Removes `entity_id` from the set associated with `key`, removing the value altogether if its set becomes
+    /// empty. Returns whether the entity was in the set.
+    pub fn remove_entity(&mut self, key: &T, entity_id: EntityId) -> bool {
+        let Some((_, set)) = self.values.get_mut(key) else {
+            return false;
+        };
+        let removed = set.remove(&entity_id);
+        if set.is_empty() {
+            self.remove_value(key);
+        }
+        removed
+    }
+
+    /// Removes `key` and its set from the index, returning the set if the value was present.
+    pub fn remove_value(&mut self, key: &T) -> Option<FastHashSet<EntityId>> {
+        let (hash, set) = self.values.remove(key)?;
+        self.by_hash.remove(&hash);
+        Some(set)
+    }
+
+    /// Moves `entity_id` from the set associated with `from` to the set associated with `to`, creating the set for
+    /// `to` and removing the set for `from` as needed. Returns whether the entity was in the set for `from`.
+    ///
+    /// Fails if `to` is new and a different value with the same hash is already stored, in which case nothing is
+    /// moved.
+    pub fn move_entity(
+        &mut self,
+        from: &T,
+        to: &T,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        if from == to {
+            let was_present = self.get(from).is_some_and(|set| set.contains(&entity_id));
+            self.insert_entity(to, entity_id)?;
+            return Ok(was_present);
+        }
+        // Insert first, so a collision leaves `from` untouched.
+        self.insert_entity(to, entity_id)?;
+        Ok(self.remove_entity(from, entity_id))
+    }
+
+    /// Iterates over the values in `range` with their sets, in order. Unlike `BTreeMap::range`, a range whose start
+    /// is after its end is just empty.
+    pub fn range<R: RangeBounds<T>>(
+        &self,
+        range: R,
+    ) -> impl Iterator<Item = (&T, &FastHashSet<EntityId>)> {
+        self.checked_range(range)
+            .map(|(value, (_, set))| (value, set))
+    }
+
+    /// All entities whose value is in `range`.
+    pub fn entities_in<R: RangeBounds<T>>(&self, range: R) -> FastHashSet<EntityId> {
+        self.range(range)
+            .flat_map(|(_, set)| set)
+            .copied()
+            .collect()
+    }
+
+    fn checked_range<R: RangeBounds<T>>(
+        &self,
+        range: R,
+    ) -> impl Iterator<Item = (&T, &(HashValue, FastHashSet<EntityId>))> {
+        // `BTreeMap::range` panics on these rather than returning nothing.
+        let empty = match (range.start_bound(), range.end_bound()) {
+            (Bound::Included(lo), Bound::Included(hi)) => lo > hi,
+            (Bound::Included(lo) | Bound::Excluded(lo), Bound::Excluded(hi))
+            | (Bound::Excluded(lo), Bound::Included(hi)) => lo >= hi,
+            _ => false,
+        };
+        (!empty)
+            .then(move || self.values.range(range))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Iterates over the values in the index with their sets, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, &FastHashSet<EntityId>)> {
+        self.values.iter().map(|(value, (_, set))| (value, set))
+    }
+
+    /// Iterates over the values in the index, in order.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.values.keys()
+    }
+
+    /// The number of distinct values in the index.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The value a bound of a type-erased range query refers to.
+    fn resolve<'a>(&'a self, key: RangeKey<'a>) -> Result<Cow<'a, T>, IndexError> {
+        match key {
+            RangeKey::Hash(hash) => self
+                .by_hash
+                .get(&hash)
+                .map(Cow::Borrowed)
+                .ok_or(IndexError::UnknownHash(hash)),
+            RangeKey::Value {
+                value,
+                type_name: found,
+            } => value
+                .downcast_ref::<T>()
+                .map(Cow::Borrowed)
+                .ok_or(IndexError::TypeMismatch {
+                    expected: type_name::<T>(),
+                    found,
+                }),
+            #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+            RangeKey::Serialized { format, bytes } => {
+                Ok(Cow::Owned(index_codec::decode_key(bytes, format)?))
+            }
+        }
+    }
+
+    fn resolve_bound<'a>(
+        &'a self,
+        bound: Bound<RangeKey<'a>>,
+    ) -> Result<Bound<Cow<'a, T>>, IndexError> {
+        Ok(match bound {
+            Bound::Included(key) => Bound::Included(self.resolve(key)?),
+            Bound::Excluded(key) => Bound::Excluded(self.resolve(key)?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    }
+}
+
+/// One bound of a type-erased range query. See the [module documentation](self).
+#[derive(Copy, Clone, Debug)]
+pub enum RangeKey<'a> {
+    /// The hash of a value that's in the index. Hashes don't preserve order, so this can't name a value the index
+    /// doesn't contain.
+    Hash(HashValue),
+    /// A value of the index's value type. Build one with [`RangeKey::value`], which records the name of its type for
+    /// [`IndexError::TypeMismatch`].
+    Value {
+        value: &'a dyn Any,
+        /// The name of the value's type, as given by [`std::any::type_name`].
+        type_name: &'static str,
+    },
+    /// A value serialized with [`encode_key`](index_codec::encode_key). Its type must have a registered codec.
+    #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+    Serialized {
+        format: IndexFormat,
+        bytes: &'a [u8],
+    },
+}
+
+impl<'a> RangeKey<'a> {
+    /// A bound given by the value itself.
+    pub fn value<V: Any>(value: &'a V) -> Self {
+        Self::Value {
+            value,
+            type_name: type_name::<V>(),
+        }
+    }
+}
+
+/// The type-erased API of an [`OrderedIndex`]: a [`TypeErasedIndex`] that also answers range queries.
+pub trait TypeErasedRangeIndex: TypeErasedIndex {
+    /// The hash and set of each value between `lower` and `upper`, in order.
+    fn hashes_in_range(
+        &self,
+        lower: Bound<RangeKey<'_>>,
+        upper: Bound<RangeKey<'_>>,
+    ) -> Result<Vec<(HashValue, &dyn EntitySet)>, IndexError>;
+
+    /// All entities whose value is between `lower` and `upper`.
+    fn entities_in_range(
+        &self,
+        lower: Bound<RangeKey<'_>>,
+        upper: Bound<RangeKey<'_>>,
+    ) -> Result<FastHashSet<EntityId>, IndexError> {
+        Ok(self
+            .hashes_in_range(lower, upper)?
+            .into_iter()
+            .flat_map(|(_, set)| set.iter_ids())
+            .collect())
+    }
+}
+
+/// A "boxed" `TypeErasedRangeIndex`
+pub type BxRangeIndex = Box<dyn TypeErasedRangeIndex>;
+
+impl<T: Ord + Hash + Clone + Any, S: BuildHasher128 + 'static> TypeErasedRangeIndex
+    for OrderedIndex<T, S>
+{
+    fn hashes_in_range(
+        &self,
+        lower: Bound<RangeKey<'_>>,
+        upper: Bound<RangeKey<'_>>,
+    ) -> Result<Vec<(HashValue, &dyn EntitySet)>, IndexError> {
+        let lower = self.resolve_bound(lower)?;
+        let upper = self.resolve_bound(upper)?;
+        Ok(self
+            .checked_range((
+                lower.as_ref().map(|key| key.as_ref()),
+                upper.as_ref().map(|key| key.as_ref()),
+            ))
+            .map(|(_, (hash, set))| (*hash, set as &dyn EntitySet))
+            .collect())
+    }
+}
+
+impl<T: Ord + Hash + Clone + Any, S: BuildHasher128 + 'static> TypeErasedIndex
+    for OrderedIndex<T, S>
+{
+    fn insert_entity_with_hash(
+        &mut self,
+        hash: HashValue,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        let set = self.try_get_with_hash_mut(hash)?;
+        Ok(set.insert(entity_id))
+    }
+
+    fn get_with_hash(&self, hash: HashValue) -> Option<&dyn EntitySet> {
+        let set = self.get(self.by_hash.get(&hash)?)?;
+        Some(set)
+    }
+
+    fn get_with_hash_mut(&mut self, hash: HashValue) -> Option<&mut dyn EntitySet> {
+        let key = self.by_hash.get(&hash)?;
+        self.values
+            .get_mut(key)
+            .map(|(_, set)| set as &mut dyn EntitySet)
+    }
+
+    fn has_hash(&self, hash: HashValue) -> bool {
+        self.by_hash.contains_key(&hash)
+    }
+
+    fn remove_entity_with_hash(&mut self, hash: HashValue, entity_id: EntityId) -> bool {
+        let Some(key) = self.by_hash.get(&hash) else {
+            return false;
+        };
+        let Some((_, set)) = self.values.get_mut(key) else {
+            return false;
+        };
+        let removed = set.remove(&entity_id);
+        if set.is_empty() {
+            self.values.remove(key);
+            self.by_hash.remove(&hash);
+        }
+        removed
+    }
+
+    fn move_entity_between_hashes(
+        &mut self,
+        from: HashValue,
+        to: HashValue,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        if !self.has_hash(to) {
+            return Err(IndexError::UnknownHash(to));
+        }
+        if from == to {
+            return self
+                .insert_entity_with_hash(to, entity_id)
+                .map(|inserted| !inserted);
+        }
+        self.insert_entity_with_hash(to, entity_id)?;
+        Ok(self.remove_entity_with_hash(from, entity_id))
+    }
+
+    /// Iterates in the order of the values, not their hashes.
+    fn iter_hashes(&self) -> Box<dyn Iterator<Item = (HashValue, &dyn EntitySet)> + '_> {
+        Box::new(
+            self.values
+                .values()
+                .map(|(hash, set)| (*hash, set as &dyn EntitySet)),
+        )
+    }
+
+    fn for_each_hash(&self, f: &mut dyn FnMut(HashValue, &dyn EntitySet)) {
+        for (hash, set) in self.values.values() {
+            f(*hash, set);
+        }
+    }
+
+    /// Writes the values in order. The checkpoint loads back as an `OrderedIndex<T>` once `T` is registered with
+    /// [`register_ordered_index_codec`](index_codec::register_ordered_index_codec).
+    #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+    fn serialize_into(
+        &self,
+        writer: &mut dyn std::io::Write,
+        format: IndexFormat,
+    ) -> Result<(), IndexError> {
+        let shape =
+            IndexShape::of::<S, FastHashSet<EntityId>>(IndexKind::Ordered, &self.build_hasher)?;
+        let entries = self
+            .iter()
+            .map(|(value, set)| (value, set as &dyn EntitySet));
+        Ok(index_codec::serialize_values(
+            shape, entries, writer, format,
+        )?)
+    }
+
+    /// Computed on every call, unlike an `Index<T>`'s. There's no `HashTable`, so `buckets` is 0.
+    fn stats(&self) -> IndexStats {
+        let sizes: SetSizes = self
+            .values
+            .values()
+            .map(|(_, set)| SetSize::of(set))
+            .collect();
+        // A `BTreeMap` doesn't report its allocation, so count one entry per value.
+        let table_bytes = self.values.len()
+            * std::mem::size_of::<(T, (HashValue, FastHashSet<EntityId>))>()
+            + self.by_hash.allocation_size();
+        sizes.stats(table_bytes, 0)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(entity_ids: impl IntoIterator<Item = EntityId>) -> FastHashSet<EntityId> {
+        entity_ids.into_iter().collect()
+    }
+
+    /// Entity `n` is `10 * n` years old.
+    fn ages() -> OrderedIndex<u32> {
+        let mut index = OrderedIndex::new();
+        for entity_id in 0..10 {
+            index
+                .insert_entity(&(10 * entity_id as u32), entity_id)
+                .unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn typed_ranges_walk_values_in_order() {
+        let index = ages();
+        assert_eq!(index.entities_in(18..65), set([2, 3, 4, 5, 6]));
+        assert_eq!(index.entities_in(..=20), set([0, 1, 2]));
+        assert_eq!(
+            index.range(75..).map(|(age, _)| *age).collect::<Vec<_>>(),
+            [80, 90]
+        );
+        // Backwards and empty ranges don't panic.
+        let (lo, hi) = (65, 18);
+        assert!(index.entities_in(lo..hi).is_empty());
+        assert!(index.entities_in(20..20).is_empty());
+        assert_eq!(
+            index.entities_in((Bound::Excluded(20), Bound::Included(30))),
+            set([3])
+        );
+    }
+
+    #[test]
+    fn erased_ranges_take_hashes_and_values() {
+        let index: BxRangeIndex = Box::new(ages());
+        let thirty = Fingerprint::of(&30u32);
+        let sixty = Fingerprint::of(&60u32);
+        assert_eq!(
+            index
+                .entities_in_range(
+                    Bound::Included(RangeKey::Hash(thirty)),
+                    Bound::Excluded(RangeKey::Hash(sixty))
+                )
+                .unwrap(),
+            set([3, 4, 5])
+        );
+        assert_eq!(
+            index
+                .entities_in_range(Bound::Excluded(RangeKey::value(&65u32)), Bound::Unbounded)
+                .unwrap(),
+            set([7, 8, 9])
+        );
+        // The hashes come back in the order of the values.
+        let hashes: Vec<_> = index
+            .hashes_in_range(Bound::Unbounded, Bound::Included(RangeKey::value(&10u32)))
+            .unwrap()
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .collect();
+        assert_eq!(hashes, [Fingerprint::of(&0u32), Fingerprint::of(&10u32)]);
+
+        // A hash can only name a value that's in the index.
+        assert!(matches!(
+            index.entities_in_range(
+                Bound::Included(RangeKey::Hash(Fingerprint::of(&35u32))),
+                Bound::Unbounded
+            ),
+            Err(IndexError::UnknownHash(_))
+        ));
+        assert!(matches!(
+            index.entities_in_range(Bound::Included(RangeKey::value(&35u64)), Bound::Unbounded),
+            Err(IndexError::TypeMismatch {
+                expected: "u32",
+                found: "u64"
+            })
+        ));
+    }
+
+    #[cfg(feature = "serde-json")]
+    #[test]
+    fn erased_ranges_take_serialized_bounds() {
+        index_codec::register_index_codec::<u32>("u32");
+        let index: BxRangeIndex = Box::new(ages());
+        let lo = index_codec::encode_key(&18u32, IndexFormat::Json).unwrap();
+        assert_eq!(lo, b"18");
+        let adults = index
+            .entities_in_range(
+                Bound::Included(RangeKey::Serialized {
+                    format: IndexFormat::Json,
+                    bytes: &lo,
+                }),
+                Bound::Excluded(RangeKey::Serialized {
+                    format: IndexFormat::Json,
+                    bytes: b"65",
+                }),
+            )
+            .unwrap();
+        assert_eq!(adults, set([2, 3, 4, 5, 6]));
+
+        let error = index
+            .entities_in_range(
+                Bound::Included(RangeKey::Serialized {
+                    format: IndexFormat::Json,
+                    bytes: b"\"eighteen\"",
+                }),
+                Bound::Unbounded,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            IndexError::Serialization(index_codec::CodecError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn erased_api_matches_the_hash_index() {
+        let mut index = ages();
+        let (ten, twenty) = (index.hash_value(&10), index.hash_value(&20));
+        assert!(index.insert_entity_with_hash(ten, 11).unwrap());
+        assert_eq!(index.get(&10), Some(&set([1, 11])));
+        let fifteen = index.hash_value(&15);
+        assert!(matches!(
+            index.insert_entity_with_hash(fifteen, 1),
+            Err(IndexError::UnknownHash(hash)) if hash == fifteen
+        ));
+
+        assert!(index.move_entity_between_hashes(ten, twenty, 1).unwrap());
+        assert!(index.move_entity_between_hashes(ten, twenty, 11).unwrap());
+        assert!(!index.has_hash(ten));
+        assert_eq!(index.get(&10), None);
+        assert_eq!(
+            index.get_with_hash(twenty).map(|set| set.to_hash_set()),
+            Some(set([1, 2, 11]))
+        );
+
+        assert!(index.remove_entity_with_hash(twenty, 2));
+        assert!(index.move_entity(&20, &15, 1).unwrap());
+        assert_eq!(
+            index.values().copied().take(3).collect::<Vec<_>>(),
+            [0, 15, 20]
+        );
+        assert_eq!(index.remove_value(&20), Some(set([11])));
+        assert!(!index.has_hash(twenty));
+        assert_eq!(index.len(), 9);
+
+        let stats = index.stats();
+        assert_eq!(
+            (
+                stats.distinct_values,
+                stats.total_entities,
+                stats.largest_set,
+                stats.median_set
+            ),
+            (9, 9, 1, 1)
+        );
+        assert_eq!(stats.buckets, 0);
+    }
+
+    #[test]
+    fn colliding_values_are_rejected() {
+        use crate::hashing::TruncatingBuildHasher;
+
+        // Every value hashes to zero.
+        let mut index = OrderedIndex::<u32, _>::with_hasher(TruncatingBuildHasher::new(0));
+        index.insert_entity(&1, 1).unwrap();
+        let error = index.insert_entity(&2, 2).unwrap_err();
+        assert!(matches!(
+            error,
+            IndexError::Collision {
+                hash: Fingerprint::ZERO,
+                value_type: "u32",
+                ..
+            }
+        ));
+        assert_eq!(error.colliding_values::<u32>(), Some((&1, &2)));
+        assert_eq!(index.len(), 1);
+    }
+}
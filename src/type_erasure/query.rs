@@ -0,0 +1,292 @@
+/*!
+
+# Queries Across Indexes
+
+A query like "red cars made in 2019" is a set of predicates, each a value in some index, and the
+answer is the intersection (or, for "red or blue", the union) of their entity sets. A [`Query`]
+collects predicates as `(index, hash)` pairs over type-erased indexes, so one query can mix
+indexes of any value types. [`Query::matching`] takes a typed key and hashes it with its
+[`Index`]; [`Query::matching_hash`] takes a hash directly.
+
+Running a query doesn't build any intermediate sets. It orders the predicates by selectivity,
+estimated by the size of each set, and then walks one set at a time:
+
+- An intersection walks the _smallest_ set and keeps the entities every other set contains, so it
+  costs a probe per predicate per entity of the smallest set, however large the others are.
+- A union walks the sets from largest to smallest and skips entities an earlier set already
+  yielded, so that each entity comes out once.
+
+## The type of the result
+
+The natural way to write this is with iterator combinators: `smallest.iter().filter(...)` for an
+intersection, `sets.iter().enumerate().flat_map(...)` for a union. Each chain has its own type, one
+that can't be written down because it contains closures. A function can return one such type as an
+`impl Iterator`, but not "one or the other", and the opaque type leaks into every caller that wants
+to store the result, which then has to be generic or use `impl Trait` too. Change the chain and the
+type changes with it.
+
+Returning the whole chain as a `Box<dyn Iterator>` fixes the type, but the result is still
+anonymous: it has nothing but `next`, and nowhere to put documentation. Here the iterator is written
+out by hand instead. [`QueryResultIterator`] is a plain named struct holding the sets and a cursor
+into the one being walked, and both kinds of query are just states of it. It can be stored in a
+struct field, returned from a trait method, and given methods of its own.
+
+*/
+
+use std::{any::Any, hash::Hash};
+
+use super::{
+    entity_set::EntitySet,
+    type_erased_api::{EntityId, HashValue, Index, TypeErasedIndex},
+};
+use crate::hashing::BuildHasher128;
+
+/// How a query combines its predicates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Combine {
+    /// Entities that match every predicate.
+    All,
+    /// Entities that match at least one predicate.
+    Any,
+}
+
+/// A set of predicates over type-erased indexes. See the [module documentation](self).
+#[derive(Clone)]
+pub struct Query<'a> {
+    combine: Combine,
+    predicates: Vec<(&'a dyn TypeErasedIndex, HashValue)>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(combine: Combine) -> Self {
+        Self {
+            combine,
+            predicates: Vec::new(),
+        }
+    }
+
+    /// A query for the entities that match every predicate. With no predicates it matches nothing.
+    pub fn all() -> Self {
+        Self::new(Combine::All)
+    }
+
+    /// A query for the entities that match any predicate.
+    pub fn any() -> Self {
+        Self::new(Combine::Any)
+    }
+
+    /// Adds the predicate "the entity has the value with this hash in `index`".
+    pub fn matching_hash(mut self, index: &'a dyn TypeErasedIndex, hash: HashValue) -> Self {
+        self.predicates.push((index, hash));
+        self
+    }
+
+    /// Adds the predicate "the entity has `key` in `index`".
+    pub fn matching<T, S, E>(self, index: &'a Index<T, S, E>, key: &T) -> Self
+    where
+        T: Hash + Eq + Clone + Any,
+        S: BuildHasher128 + 'static,
+        E: EntitySet,
+    {
+        let hash = index.hash_value(key);
+        self.matching_hash(index, hash)
+    }
+
+    pub fn combine(&self) -> Combine {
+        self.combine
+    }
+
+    /// The positions of the predicates in the order the query evaluates them.
+    pub fn plan(&self) -> Vec<usize> {
+        self.planned_sets()
+            .into_iter()
+            .map(|(_, position, _)| position)
+            .collect()
+    }
+
+    /// Each predicate's set, looked up once, with its size and the predicate's position, in the order the query
+    /// evaluates them. A value no entity has has no set, and a size of 0.
+    fn planned_sets(&self) -> Vec<(usize, usize, Option<&'a dyn EntitySet>)> {
+        let mut sets: Vec<_> = self
+            .predicates
+            .iter()
+            .enumerate()
+            .map(|(position, &(index, hash))| {
+                let set = index.get_with_hash(hash);
+                (set.map_or(0, |set| set.len()), position, set)
+            })
+            .collect();
+        match self.combine {
+            Combine::All => sets.sort_by_key(|&(len, ..)| len),
+            Combine::Any => sets.sort_by_key(|&(len, ..)| std::cmp::Reverse(len)),
+        }
+        sets
+    }
+
+    /// Runs the query. Entities come out one at a time, each once.
+    pub fn run(&self) -> QueryResultIterator<'a> {
+        let planned = self.planned_sets();
+        // No entity has the value, so none matches every predicate.
+        if self.combine == Combine::All && planned.iter().any(|(.., set)| set.is_none()) {
+            return QueryResultIterator::new(self.combine, Vec::new());
+        }
+        let sets = planned.into_iter().filter_map(|(.., set)| set).collect();
+        QueryResultIterator::new(self.combine, sets)
+    }
+}
+
+/// The entities a [`Query`] matches. A named type, so it can be stored and returned without naming an iterator
+/// chain; see the [module documentation](self).
+pub struct QueryResultIterator<'a> {
+    combine: Combine,
+    /// The sets to combine, in the query's plan order.
+    sets: Vec<&'a dyn EntitySet>,
+    /// The set being walked.
+    current: usize,
+    /// The entities of `sets[current]` not yet looked at.
+    entities: Box<dyn Iterator<Item = EntityId> + 'a>,
+}
+
+impl<'a> QueryResultIterator<'a> {
+    fn new(combine: Combine, sets: Vec<&'a dyn EntitySet>) -> Self {
+        let entities = match sets.first() {
+            Some(set) => set.iter_ids(),
+            None => Box::new(std::iter::empty()),
+        };
+        Self {
+            combine,
+            sets,
+            current: 0,
+            entities,
+        }
+    }
+
+    /// Whether the query keeps `entity_id`, which comes from the current set.
+    fn keeps(&self, entity_id: EntityId) -> bool {
+        match self.combine {
+            Combine::All => self.sets[1..].iter().all(|set| set.contains(entity_id)),
+            // Yielded already, if an earlier set has it.
+            Combine::Any => !self.sets[..self.current]
+                .iter()
+                .any(|set| set.contains(entity_id)),
+        }
+    }
+}
+
+impl Iterator for QueryResultIterator<'_> {
+    type Item = EntityId;
+
+    fn next(&mut self) -> Option<EntityId> {
+        loop {
+            match self.entities.next() {
+                Some(entity_id) if self.keeps(entity_id) => return Some(entity_id),
+                Some(_) => {}
+                // An intersection only walks the first set.
+                None if self.combine == Combine::Any && self.current + 1 < self.sets.len() => {
+                    self.current += 1;
+                    self.entities = self.sets[self.current].iter_ids();
+                }
+                None => return None,
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match self.combine {
+            Combine::All => self.sets.first().map_or(0, |set| set.len()),
+            Combine::Any => self.sets[self.current.min(self.sets.len())..]
+                .iter()
+                .map(|set| set.len())
+                .sum(),
+        };
+        (0, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hashing::{FastHashSet, Xxh3BuildHasher},
+        type_erasure::{entity_set::SortedVecSet, type_erased_api::BxIndex},
+    };
+
+    /// Colors and ages of entities `0..10`: even entities are red and odd ones blue, and entity `n` is `20 + n / 3`.
+    fn people() -> (Index<&'static str>, Index<u8>) {
+        let (mut colors, mut ages) = (Index::new(), Index::new());
+        for entity_id in 0..10 {
+            let color = if entity_id % 2 == 0 { "red" } else { "blue" };
+            colors.insert_entity(&color, entity_id).unwrap();
+            ages.insert_entity(&(20 + entity_id as u8 / 3), entity_id)
+                .unwrap();
+        }
+        (colors, ages)
+    }
+
+    fn sorted(entity_ids: impl Iterator<Item = EntityId>) -> Vec<EntityId> {
+        let mut entity_ids: Vec<_> = entity_ids.collect();
+        entity_ids.sort_unstable();
+        entity_ids
+    }
+
+    #[test]
+    fn intersections_keep_entities_matching_every_predicate() {
+        let (colors, ages) = people();
+        let query = Query::all().matching(&colors, &"red").matching(&ages, &21);
+        assert_eq!(sorted(query.run()), [4]);
+        // The smaller set, age 21, is walked first.
+        assert_eq!(query.plan(), [1, 0]);
+
+        let query = Query::all()
+            .matching(&colors, &"blue")
+            .matching(&ages, &20)
+            .matching(&ages, &21);
+        assert_eq!(query.run().count(), 0);
+    }
+
+    #[test]
+    fn unions_yield_each_entity_once() {
+        let (colors, ages) = people();
+        let query = Query::any().matching(&ages, &23).matching(&colors, &"red");
+        assert_eq!(sorted(query.run()), [0, 2, 4, 6, 8, 9]);
+        // The larger set, red, is walked first.
+        assert_eq!(query.plan(), [1, 0]);
+    }
+
+    #[test]
+    fn missing_values_match_nothing() {
+        let (colors, ages) = people();
+        let all = Query::all().matching(&colors, &"red").matching(&ages, &99);
+        assert_eq!(all.run().next(), None);
+        let any = Query::any()
+            .matching(&colors, &"green")
+            .matching(&ages, &23);
+        assert_eq!(sorted(any.run()), [9]);
+        assert_eq!(Query::all().run().next(), None);
+    }
+
+    #[test]
+    fn erased_and_typed_predicates_mix() {
+        let (colors, ages) = people();
+        let mut sorted_ages = Index::<u8, Xxh3BuildHasher, SortedVecSet>::default();
+        for (age, set) in ages.iter() {
+            sorted_ages
+                .insert_value(*age, set.iter().copied().collect())
+                .unwrap();
+        }
+        let red = colors.hash_value(&"red");
+        let indexes: Vec<BxIndex> = vec![Box::new(colors), Box::new(sorted_ages)];
+
+        // A generic planner that only has hashes.
+        let query = Query::all()
+            .matching_hash(indexes[0].as_ref(), red)
+            .matching_hash(indexes[1].as_ref(), ages.hash_value(&22));
+        let mut results = query.run();
+        assert!(results.size_hint().1.is_some_and(|bound| bound <= 3));
+        assert_eq!(
+            results.by_ref().collect::<FastHashSet<_>>(),
+            FastHashSet::from_iter([6, 8])
+        );
+        assert_eq!(results.next(), None);
+    }
+}
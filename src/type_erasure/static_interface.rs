@@ -18,8 +18,29 @@ Both strategies use the same technique of generating static constant data
 
 */
 
-/// Our example trait implemented by some concrete type `M` provides an interface to static methods or data.
-pub trait MyTrait {
+/// The fully qualified path of a type, used to compute a stable ID for it at compile time. Implement it with
+/// [`type_path_impl!`](crate::type_path_impl) rather than by hand, so the path can't be mistyped.
+pub trait TypePath {
+    const TYPE_PATH: &'static str;
+}
+
+/// Implements [`TypePath`] for a type defined in the calling module.
+///
+/// The path is `module_path!()` plus the type's name, and `module_path!()` doesn't include the function a type is
+/// declared in. Two types with the same name declared inside different functions of one module therefore get the
+/// same path, and the same stable ID.
+#[macro_export]
+macro_rules! type_path_impl {
+    ($type_name:ident) => {
+        impl $crate::type_erasure::static_interface::TypePath for $type_name {
+            const TYPE_PATH: &'static str = concat!(module_path!(), "::", stringify!($type_name));
+        }
+    };
+}
+
+/// Our example trait implemented by some concrete type `M` provides an interface to static methods or data. Its
+/// [`TypePath`] supertrait gives every implementor a stable ID.
+pub trait MyTrait: TypePath {
     fn get_static_dependency_data() -> &'static MyStaticData;
 }
 
@@ -81,14 +102,18 @@ pub mod simple_static_vtable {
 
     */
 
-    use std::marker::PhantomData;
+    use core::marker::PhantomData;
 
     use super::*;
+    use crate::hashing::{Fingerprint, xxh3_128};
 
     /// Function VTable describing the static interface we care about.
     #[derive(Copy, Clone)]
     pub struct StaticMyTraitInterface {
         get_static_dependency_data: fn() -> &'static MyStaticData,
+        /// A hash of `M::TYPE_PATH`. The vtable's address identifies `M` only within one run of one build; this
+        /// identifies it everywhere.
+        stable_id: Fingerprint,
     }
 
     impl StaticMyTraitInterface {
@@ -96,6 +121,7 @@ pub mod simple_static_vtable {
         pub const fn of<M: MyTrait>() -> Self {
             Self {
                 get_static_dependency_data: M::get_static_dependency_data,
+                stable_id: Fingerprint::new(xxh3_128(M::TYPE_PATH.as_bytes())),
             }
         }
 
@@ -103,6 +129,10 @@ pub mod simple_static_vtable {
         pub fn get_data(&self) -> &'static MyStaticData {
             (self.get_static_dependency_data)()
         }
+
+        pub const fn stable_id(&self) -> Fingerprint {
+            self.stable_id
+        }
     }
 
     /// Per-`M` holder that exposes an associated `const` vtable.
@@ -135,6 +165,8 @@ pub mod simple_static_vtable {
             msg: "hello from MockType",
         };
 
+        crate::type_path_impl!(MockType);
+
         impl MyTrait for MockType {
             fn get_static_dependency_data() -> &'static MyStaticData {
                 &MOCK_DATA
@@ -168,6 +200,8 @@ pub mod simple_static_vtable {
             static ANOTHER_DATA: MyStaticData = MyStaticData {
                 msg: "another type",
             };
+            crate::type_path_impl!(AnotherType);
+
             impl MyTrait for AnotherType {
                 fn get_static_dependency_data() -> &'static MyStaticData {
                     &ANOTHER_DATA
@@ -192,22 +226,29 @@ pub mod simple_static_vtable {
 
         #[test]
         fn static_vtables_are_distinct_per_concrete_type() {
-            struct AnotherType;
+            struct OtherConcreteType;
             static ANOTHER_DATA: MyStaticData = MyStaticData {
                 msg: "another type",
             };
 
-            impl MyTrait for AnotherType {
+            crate::type_path_impl!(OtherConcreteType);
+
+            impl MyTrait for OtherConcreteType {
                 fn get_static_dependency_data() -> &'static MyStaticData {
                     &ANOTHER_DATA
                 }
             }
 
             let t1 = tag_for::<MockType>();
-            let t2 = tag_for::<AnotherType>();
+            let t2 = tag_for::<OtherConcreteType>();
 
             // Different vtables => different addresses
             assert_ne!(t1 as *const _, t2 as *const _);
+            // ...and different stable IDs, which are computed at compile time.
+            assert_ne!(t1.stable_id(), t2.stable_id());
+            const MOCK_ID: Fingerprint =
+                crate::const_fingerprint!(concat!(module_path!(), "::MockType"));
+            assert_eq!(t1.stable_id(), MOCK_ID);
 
             assert_eq!(t1.get_data().msg, "hello from MockType");
             assert_eq!(t2.get_data().msg, "another type");
@@ -285,6 +326,8 @@ pub mod static_reference_to_zst {
             msg: "hello from MockType",
         };
 
+        crate::type_path_impl!(MockType);
+
         impl MyTrait for MockType {
             fn get_static_dependency_data() -> &'static MyStaticData {
                 &MOCK_DATA
@@ -308,6 +351,8 @@ pub mod static_reference_to_zst {
                 msg: "another type",
             };
 
+            crate::type_path_impl!(AnotherType);
+
             impl MyTrait for AnotherType {
                 fn get_static_dependency_data() -> &'static MyStaticData {
                     &ANOTHER_DATA
@@ -24,81 +24,490 @@ sense. These rules of thumb are not an excuse to turn off your brain.
 
 */
 
-use std::{any::Any, collections::HashSet, hash::Hash};
+#[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+use std::io::Write;
+use std::{
+    any::{Any, type_name},
+    borrow::Cow,
+    fmt::{Debug, Display, Formatter},
+    hash::Hash,
+};
 
-use hashbrown::{HashTable, hash_table::OccupiedEntry};
+use hashbrown::{HashTable, hash_table::Entry};
 
-use crate::hashing::one_shot_128;
+#[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+use super::index_codec::{self, CodecError, IndexFormat, IndexKind, IndexShape};
+use super::{
+    entity_set::EntitySet,
+    index_stats::{IndexStats, SetSize, SetSizes},
+};
+use crate::hashing::{BuildHasher128, FastHashSet, Fingerprint, Hashed, Xxh3BuildHasher};
 
 /// A "boxed" `TypeErasedIndex`, use anywhere you need a type-erased `Index<T>`
 pub type BxIndex = Box<dyn TypeErasedIndex>;
 
 pub type EntityId = u64;
-pub type HashValue = u128;
+/// The full 128-bit hash of a key, which the type-erased API uses in place of the key itself.
+pub type HashValue = Fingerprint;
+
+/// Whether the typed insert paths double-check the index's "equal hashes means equal values" assumption. On in debug
+/// builds and whenever the `collision-audit` feature is enabled. See [`IndexError::Collision`].
+pub const AUDIT_COLLISIONS: bool = cfg!(any(debug_assertions, feature = "collision-audit"));
+
+/// Whether the type-erased inserts re-hash the stored value to check it still has its hash. Only on with the
+/// `collision-audit` feature, because it hashes a stored key again on every insert. See [`IndexError::HashChanged`].
+pub const AUDIT_STORED_HASHES: bool = cfg!(feature = "collision-audit");
+
+/// Why an operation on an index failed.
+#[derive(Debug)]
+pub enum IndexError {
+    /// No value in the index has this hash. The type-erased API can only reach values that are already in the index.
+    UnknownHash(HashValue),
+    /// A value of another type than the index's values was passed where one of its values was expected, or an index
+    /// was downcast to the wrong type. Both types are named by [`std::any::type_name`].
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// Two distinct values with the same 128-bit hash.
+    ///
+    /// `Index<T>` identifies a value by its 128-bit hash and never compares the values themselves. With a good hasher
+    /// that's sound: we do not expect a collision before the heat death of the universe. But it breaks _silently_ if
+    /// someone swaps in a weak hasher, or writes a `Hash` impl that skips a field `Eq` looks at, so that two unequal
+    /// values are merged into one entry. When [`AUDIT_COLLISIONS`] is on, the typed API compares the stored value
+    /// with the incoming one using `Eq` whenever their hashes match and reports this error instead of merging them.
+    ///
+    /// The error carries both values, for code that knows their type to look at with
+    /// [`colliding_values`](Self::colliding_values). Nothing requires them to implement `Debug`, so the message only
+    /// names their type.
+    Collision {
+        hash: HashValue,
+        /// The name of the type of the colliding values, as given by [`std::any::type_name`].
+        value_type: &'static str,
+        /// The value already in the index.
+        stored: Box<dyn Any>,
+        /// The value that was being inserted.
+        incoming: Box<dyn Any>,
+    },
+    /// The value stored under `hash` no longer has that hash, so the typed API can't find it. It changed after it
+    /// was inserted, through a `Hash` impl that depends on interior mutability, for example. Only reported when
+    /// [`AUDIT_STORED_HASHES`] is on.
+    HashChanged {
+        hash: HashValue,
+        /// The name of the type of the stored value, as given by [`std::any::type_name`].
+        value_type: &'static str,
+    },
+    /// The index's set type can't hold this entity ID, see [`EntitySet::max_entity_id`]. Nothing was inserted.
+    EntityOutOfRange {
+        entity_id: EntityId,
+        /// The largest ID the set type can hold.
+        max: EntityId,
+    },
+    /// The index couldn't be written, or a serialized value couldn't be read.
+    #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+    Serialization(CodecError),
+}
+
+impl Display for IndexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownHash(hash) => write!(f, "no value in the index has the hash {hash}"),
+            Self::TypeMismatch { expected, found } => {
+                write!(f, "expected a value of type `{expected}`, found `{found}`")
+            }
+            Self::Collision {
+                hash, value_type, ..
+            } => write!(
+                f,
+                "hash collision: two unequal values of type `{value_type}` have the hash {hash}"
+            ),
+            Self::HashChanged { hash, value_type } => write!(
+                f,
+                "the hash of a value of type `{value_type}` changed from {hash} while it was in the index"
+            ),
+            Self::EntityOutOfRange { entity_id, max } => write!(
+                f,
+                "the entity ID {entity_id} is larger than {max}, the largest the index's sets can hold"
+            ),
+            #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+            Self::Serialization(error) => write!(f, "serialization failed: {error}"),
+        }
+    }
+}
+
+impl IndexError {
+    /// The stored and the incoming value of a [`Collision`](Self::Collision) between values of type `T`, or `None`
+    /// if this is another error.
+    pub fn colliding_values<T: Any>(&self) -> Option<(&T, &T)> {
+        match self {
+            Self::Collision {
+                stored, incoming, ..
+            } => Some((stored.downcast_ref()?, incoming.downcast_ref()?)),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for IndexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+            Self::Serialization(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+impl From<CodecError> for IndexError {
+    fn from(error: CodecError) -> Self {
+        Self::Serialization(error)
+    }
+}
 
 /// The typed `Index<T>`
+///
+/// The index is generic over the [`BuildHasher128`] that computes the 128-bit hashes of its keys.
+/// The default, [`Xxh3BuildHasher`] with the default seed, agrees with
+/// [`one_shot_128`](crate::hashing::one_shot_128). Callers of the type-erased API must compute
+/// hashes with the same hasher the index uses; [`Index::hash_value`] does this for you.
+///
+/// Each key is hashed exactly once, when it's inserted or looked up. Its hash is stored next to it as a
+/// [`Hashed<T>`], so growing the table and probing stored entries never hash a stored key again.
+///
+/// The set of entities for each value is an `E`, any [`EntitySet`]. See the
+/// [`entity_set`](super::entity_set) module for how to choose one.
 #[derive(Default)]
-pub struct Index<T: Hash + Eq + Clone + Any> {
+pub struct Index<
+    T: Hash + Eq + Clone + Any,
+    S: BuildHasher128 = Xxh3BuildHasher,
+    E: EntitySet = FastHashSet<EntityId>,
+> {
     /// We store a copy of the value here so that we can iterate over it in the typed API, and so that the type-erased
     /// API can access some serialization of it.
-    lookup: HashTable<(T, HashSet<EntityId>)>,
+    lookup: HashTable<IndexEntry<T, E>>,
+    build_hasher: S,
+    /// The sizes of the sets in `lookup`, kept up to date for [`stats`](TypeErasedIndex::stats).
+    sizes: SetSizes,
+    /// The hash of the last set handed out by `&mut`, and its size at the time. See [`settle`](Self::settle).
+    lent: Option<(HashValue, SetSize)>,
+}
+
+/// A stored key with its cached hash, and the set of entities associated with it.
+pub type IndexEntry<T, E = FastHashSet<EntityId>> = (Hashed<T>, E);
+
+/// The hash the table files `entry` under. This must be the hash each entry was inserted with.
+fn table_hash<T, E>((stored_value, _): &IndexEntry<T, E>) -> u64 {
+    stored_value.fingerprint().as_u64()
+}
+
+/// Equality is determined by comparing the full 128-bit hashes. We do not expect any collisions before the heat death
+/// of the universe.
+fn hash128_equality<T, E>(hash: HashValue) -> impl Fn(&IndexEntry<T, E>) -> bool {
+    move |(stored_value, _)| stored_value.fingerprint() == hash
 }
 
-/// Contains the typed API
 impl<T: Hash + Eq + Clone + Any> Index<T> {
     pub fn new() -> Self {
+        Self::with_hasher(Xxh3BuildHasher::default())
+    }
+}
+
+/// Contains the typed API
+impl<T: Hash + Eq + Clone + Any, S: BuildHasher128 + 'static, E: EntitySet> Index<T, S, E> {
+    /// Creates an index that hashes its keys with `build_hasher`, e.g. a seeded
+    /// [`Xxh3BuildHasher`] for domain separation.
+    pub fn with_hasher(build_hasher: S) -> Self {
         Self {
             lookup: HashTable::default(),
+            build_hasher,
+            sizes: SetSizes::default(),
+            lent: None,
         }
     }
 
+    /// The hasher the index computes its hashes with.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// The hash of `key` as computed by this index. This is the hash the type-erased API expects.
+    pub fn hash_value(&self, key: &T) -> HashValue {
+        Fingerprint::new(self.build_hasher.hash_one_128(key))
+    }
+
+    /// Wraps `key` with its hash as computed by this index. Passing a `Hashed<T>` to the `*_hashed` methods saves
+    /// hashing the key again when it's used more than once.
+    pub fn hashed(&self, key: T) -> Hashed<T> {
+        Hashed::from_parts(self.hash_value(&key), key)
+    }
+
     /// Inserts an entity into the set associated with `key`, creating a new set if one does
-    /// not yet exist. Returns a `bool` according to whether the `entity_id` already existed
-    /// in the set. Observe that several of these just defer to the untyped implementation.
-    pub fn insert_entity(&mut self, key: &T, entity_id: EntityId) -> bool {
-        let hash = one_shot_128(&key);
+    /// not yet exist. Returns whether `entity_id` was newly inserted.
+    ///
+    /// Fails if the index's set type can't hold `entity_id`, or when [`AUDIT_COLLISIONS`] is on and a different value
+    /// with the same hash is already stored.
+    pub fn insert_entity(&mut self, key: &T, entity_id: EntityId) -> Result<bool, IndexError> {
+        let hash = self.hash_value(key);
+        self.insert_entity_with_key_hash(hash, key, entity_id)
+    }
+
+    /// Like [`insert_entity`](Self::insert_entity), but with a key that has already been hashed by
+    /// [`hashed`](Self::hashed).
+    pub fn insert_entity_hashed(
+        &mut self,
+        key: &Hashed<T>,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        self.insert_entity_with_key_hash(key.fingerprint(), key, entity_id)
+    }
+
+    fn insert_entity_with_key_hash(
+        &mut self,
+        hash: HashValue,
+        key: &T,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        Self::check_entity_id(entity_id)?;
+        self.settle();
+        let mut created = false;
+        let mut entry = self
+            .lookup
+            .entry(hash.as_u64(), hash128_equality(hash), table_hash)
+            .or_insert_with(|| {
+                created = true;
+                (Hashed::from_parts(hash, key.clone()), E::new())
+            });
+        if created {
+            self.sizes.add(SetSize::of(&entry.get().1));
+        }
+        let (stored_value, set) = entry.get_mut();
+        Self::audit(hash, stored_value, key)?;
+        let before = SetSize::of(set);
+        let inserted = set.insert(entity_id);
+        self.sizes.resize(before, SetSize::of(set));
+        Ok(inserted)
+    }
+
+    /// Inserting a new _value_ requires the value itself. If the value is already in the index, its set is replaced
+    /// with `set`. Returns the set as stored in the index.
+    ///
+    /// Fails only when [`AUDIT_COLLISIONS`] is on and a different value with the same hash is already stored.
+    pub fn insert_value(&mut self, key: T, set: E) -> Result<&mut E, IndexError> {
+        let key = self.hashed(key);
+        self.insert_value_hashed(key, set)
+    }
+
+    /// Like [`insert_value`](Self::insert_value), but with a key that has already been hashed by
+    /// [`hashed`](Self::hashed).
+    pub fn insert_value_hashed(&mut self, key: Hashed<T>, set: E) -> Result<&mut E, IndexError> {
+        self.settle();
+        let hash = key.fingerprint();
+        let size = SetSize::of(&set);
+        match self
+            .lookup
+            .entry(hash.as_u64(), hash128_equality(hash), table_hash)
+        {
+            Entry::Occupied(entry) => {
+                Self::audit(hash, &entry.get().0, &key)?;
+                self.sizes.resize(SetSize::of(&entry.get().1), size);
+                // The caller can change the set through the returned `&mut`.
+                self.lent = Some((hash, size));
+                let stored = &mut entry.into_mut().1;
+                *stored = set;
+                Ok(stored)
+            }
+            Entry::Vacant(entry) => {
+                self.sizes.add(size);
+                self.lent = Some((hash, size));
+                Ok(&mut entry.insert((key, set)).into_mut().1)
+            }
+        }
+    }
+
+    /// The collision check behind [`AUDIT_COLLISIONS`]. `stored` and `incoming` are already known to have the same
+    /// 128-bit `hash`.
+    fn audit(hash: HashValue, stored: &T, incoming: &T) -> Result<(), IndexError> {
+        if AUDIT_COLLISIONS && stored != incoming {
+            return Err(IndexError::Collision {
+                hash,
+                value_type: type_name::<T>(),
+                stored: Box::new(stored.clone()),
+                incoming: Box::new(incoming.clone()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Refuses an `entity_id` that `E` can't hold, before anything is created for it.
+    fn check_entity_id(entity_id: EntityId) -> Result<(), IndexError> {
+        let max = E::max_entity_id();
+        if entity_id > max {
+            return Err(IndexError::EntityOutOfRange { entity_id, max });
+        }
+        Ok(())
+    }
 
-        // `hasher` is called if entries need to be moved or copied to a new table.
-        // This must return the same hash value that each entry was inserted with.
-        let hasher = |(stored_value, _stored_set): &_| one_shot_128(stored_value) as u64;
+    /// Catches `sizes` up with the set last handed out by `&mut`, whose size may have changed since. Every method that
+    /// changes the index calls this first, so that at most one set is ever out of step.
+    fn settle(&mut self) {
+        if let Some((hash, before)) = self.lent.take()
+            && let Some((_, set)) = self.lookup.find(hash.as_u64(), hash128_equality(hash))
+        {
+            self.sizes.resize(before, SetSize::of(set));
+        }
+    }
 
-        // Equality is determined by comparing the full 128-bit hashes. We do not expect any collisions before the heat
-        // death of the universe.
-        let hash128_equality = |(stored_value, _): &_| one_shot_128(stored_value) == hash;
+    /// The type-erased API has no `T` to compare against, so when [`AUDIT_STORED_HASHES`] is on, the best its inserts
+    /// can do is make sure the value stored under `hash` still has that hash.
+    fn audit_hash(&self, hash: HashValue) -> Result<(), IndexError> {
+        if !AUDIT_STORED_HASHES {
+            return Ok(());
+        }
+        if let Some((stored_value, _)) = self.lookup.find(hash.as_u64(), hash128_equality(hash))
+            && self.hash_value(stored_value) != hash
+        {
+            return Err(IndexError::HashChanged {
+                hash,
+                value_type: type_name::<T>(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Writes a checkpoint of the index as the given kind of index, for the wrappers that hold an `Index` inside.
+    #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+    pub(crate) fn serialize_as(
+        &self,
+        kind: IndexKind,
+        writer: &mut dyn Write,
+        format: IndexFormat,
+    ) -> Result<(), IndexError> {
+        let shape = IndexShape::of::<S, E>(kind, &self.build_hasher)?;
+        // Ordered by hash, so the same contents always serialize to the same bytes.
+        let mut entries: Vec<_> = self.lookup.iter().collect();
+        entries.sort_unstable_by_key(|(value, _)| value.fingerprint());
+        let entries = entries
+            .into_iter()
+            .map(|(value, set)| (value.get(), set as &dyn EntitySet));
+        Ok(index_codec::serialize_values(
+            shape, entries, writer, format,
+        )?)
+    }
+
+    /// Gets an immutable reference to the set associated with the `key` if it exists.
+    pub fn get(&self, key: &T) -> Option<&E> {
+        let hash = self.hash_value(key);
+        self.set_with_hash(hash)
+    }
+
+    /// Like [`get`](Self::get), but reports a missing `key` as [`IndexError::UnknownHash`] with its hash.
+    pub fn try_get(&self, key: &T) -> Result<&E, IndexError> {
+        let hash = self.hash_value(key);
+        self.set_with_hash(hash)
+            .ok_or(IndexError::UnknownHash(hash))
+    }
+
+    /// Like [`get`](Self::get), but with a key that has already been hashed by [`hashed`](Self::hashed).
+    pub fn get_hashed(&self, key: &Hashed<T>) -> Option<&E> {
+        self.set_with_hash(key.fingerprint())
+    }
+
+    /// Gets a mutable reference to the set associated with the `key` if it exists.
+    pub fn get_mut(&mut self, key: &T) -> Option<&mut E> {
+        let hash = self.hash_value(key);
+        self.set_with_hash_mut(hash)
+    }
+
+    /// Like [`get_mut`](Self::get_mut), but reports a missing `key` as [`IndexError::UnknownHash`] with its hash.
+    pub fn try_get_mut(&mut self, key: &T) -> Result<&mut E, IndexError> {
+        let hash = self.hash_value(key);
+        self.set_with_hash_mut(hash)
+            .ok_or(IndexError::UnknownHash(hash))
+    }
 
+    /// Like [`get_with_hash`](TypeErasedIndex::get_with_hash), but returns the set as an `E`.
+    pub fn set_with_hash(&self, hash: HashValue) -> Option<&E> {
         self.lookup
-            .entry(hash as u64, hash128_equality, hasher)
-            .or_insert_with(|| (key.clone(), HashSet::new()))
-            .get_mut()
-            .1
-            .insert(entity_id)
+            .find(hash.as_u64(), hash128_equality(hash))
+            .map(|(_, set)| set)
+    }
+
+    /// Like [`get_with_hash_mut`](TypeErasedIndex::get_with_hash_mut), but returns the set as an `E`.
+    pub fn set_with_hash_mut(&mut self, hash: HashValue) -> Option<&mut E> {
+        self.settle();
+        let (_, set) = self
+            .lookup
+            .find_mut(hash.as_u64(), hash128_equality(hash))?;
+        // The caller can change the set's size without the index knowing.
+        self.lent = Some((hash, SetSize::of(set)));
+        Some(set)
+    }
+
+    /// Removes `entity_id` from the set associated with `key`, removing the value altogether if its set becomes
+    /// empty. Returns whether the entity was in the set. Observe that we just defer to the untyped implementation.
+    pub fn remove_entity(&mut self, key: &T, entity_id: EntityId) -> bool {
+        let hash = self.hash_value(key);
+        self.remove_entity_with_hash(hash, entity_id)
+    }
+
+    /// Removes `key` and its set from the index, returning the set if the value was present.
+    pub fn remove_value(&mut self, key: &T) -> Option<E> {
+        self.settle();
+        let hash = self.hash_value(key);
+        let entry = self
+            .lookup
+            .find_entry(hash.as_u64(), hash128_equality(hash))
+            .ok()?;
+        let ((_, set), _) = entry.remove();
+        self.sizes.remove(SetSize::of(&set));
+        Some(set)
     }
 
-    /// Inserting a new _value_ requires the value itself.
-    pub fn insert_value(
+    /// Moves `entity_id` from the set associated with `from` to the set associated with `to`, e.g. when the property
+    /// the index is over changes. The set for `to` is created if it doesn't exist yet, and the set for `from` is
+    /// removed if it becomes empty. Returns whether the entity was in the set for `from`; it ends up in the set for
+    /// `to` either way.
+    ///
+    /// Fails if the index's set type can't hold `entity_id`, or when [`AUDIT_COLLISIONS`] is on and a different value
+    /// with the same hash as `to` is already stored. Either way, nothing is moved.
+    pub fn move_entity(
         &mut self,
-        key: T,
-        set: HashSet<EntityId>,
-    ) -> OccupiedEntry<'_, (T, HashSet<EntityId>)> {
-        let hash = one_shot_128(&key);
-        // `hasher` is called if entries need to be moved or copied to a new table.
-        // This must return the same hash value that each entry was inserted with.
-        let hasher = |(stored_value, _stored_set): &_| one_shot_128(stored_value) as u64;
-        self.lookup.insert_unique(hash as u64, (key, set), hasher)
-    }
-
-    /// Gets an immutable reference to the set associated with the `key` if it exists. Observe that we just defer to
-    /// the untyped implementation.
-    pub fn get(&self, key: &T) -> Option<&HashSet<EntityId>> {
-        let hash = one_shot_128(&key);
-        self.get_with_hash(hash)
+        from: &T,
+        to: &T,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        let from_hash = self.hash_value(from);
+        let to_hash = self.hash_value(to);
+        if from_hash == to_hash {
+            let was_present = self
+                .set_with_hash(from_hash)
+                .is_some_and(|set| set.contains(entity_id));
+            self.insert_entity_with_key_hash(to_hash, to, entity_id)?;
+            return Ok(was_present);
+        }
+        // Insert first, so a collision leaves `from` untouched.
+        self.insert_entity_with_key_hash(to_hash, to, entity_id)?;
+        Ok(self.remove_entity_with_hash(from_hash, entity_id))
     }
 
-    /// Gets a mutable reference to the set associated with the `key` if it exists. Observe that we just defer to
-    //   /// the untyped implementation.
-    pub fn get_mut(&mut self, key: &T) -> Option<&mut HashSet<EntityId>> {
-        let hash = one_shot_128(&key);
-        self.get_with_hash_mut(hash)
+    /// Iterates over the values in the index with their sets, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, &E)> {
+        self.lookup.iter().map(|(value, set)| (value.get(), set))
+    }
+
+    /// Iterates over the values in the index, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.lookup.iter().map(|(value, _)| value.get())
+    }
+
+    /// The number of distinct values in the index.
+    pub fn len(&self) -> usize {
+        self.lookup.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lookup.is_empty()
     }
 
     // Possibly other methods ...
@@ -110,58 +519,787 @@ pub trait TypeErasedIndex {
     ///
     /// If the set corresponding to the hash exists, inserts the `entity_id` into the associated set, returning a `bool`
     /// according to whether the `entity_id` was already in the set.
-    /// If the set does not exist, returns `Err(())`
-    fn insert_entity_with_hash(&mut self, hash: HashValue, entity_id: EntityId)
-    -> Result<bool, ()>;
+    /// If the set does not exist, returns [`IndexError::UnknownHash`], and if the set can't hold `entity_id`,
+    /// [`IndexError::EntityOutOfRange`].
+    fn insert_entity_with_hash(
+        &mut self,
+        hash: HashValue,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError>;
 
     /// Fetching a set only requires the hash.
-    fn get_with_hash(&self, hash: HashValue) -> Option<&HashSet<EntityId>>;
+    fn get_with_hash(&self, hash: HashValue) -> Option<&dyn EntitySet>;
 
     /// Fetching a set only requires the hash.
-    fn get_with_hash_mut(&mut self, hash: HashValue) -> Option<&mut HashSet<EntityId>>;
+    fn get_with_hash_mut(&mut self, hash: HashValue) -> Option<&mut dyn EntitySet>;
+
+    /// Like [`get_with_hash`](Self::get_with_hash), but reports a missing hash as [`IndexError::UnknownHash`].
+    fn try_get_with_hash(&self, hash: HashValue) -> Result<&dyn EntitySet, IndexError> {
+        self.get_with_hash(hash)
+            .ok_or(IndexError::UnknownHash(hash))
+    }
+
+    /// Like [`get_with_hash_mut`](Self::get_with_hash_mut), but reports a missing hash as
+    /// [`IndexError::UnknownHash`].
+    fn try_get_with_hash_mut(&mut self, hash: HashValue) -> Result<&mut dyn EntitySet, IndexError> {
+        self.get_with_hash_mut(hash)
+            .ok_or(IndexError::UnknownHash(hash))
+    }
 
     /// Does the index contain the given hash?
     fn has_hash(&self, hash: HashValue) -> bool;
+
+    /// Removes `entity_id` from the set associated with the hash, removing the value altogether if its set becomes
+    /// empty. Returns whether the entity was in the set.
+    fn remove_entity_with_hash(&mut self, hash: HashValue, entity_id: EntityId) -> bool;
+
+    /// Moves `entity_id` from the set associated with `from` to the set associated with `to`, removing the set for
+    /// `from` if it becomes empty. Like inserting, this requires the set for `to` to already exist.
+    ///
+    /// Returns a `bool` according to whether the `entity_id` was in the set for `from`. If the set for `to` does not
+    /// exist, returns [`IndexError::UnknownHash`] and moves nothing.
+    fn move_entity_between_hashes(
+        &mut self,
+        from: HashValue,
+        to: HashValue,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError>;
+
+    /// Iterates over the hashes in the index with their sets, in no particular order. The iterator is boxed so that
+    /// the trait stays object safe.
+    fn iter_hashes(&self) -> Box<dyn Iterator<Item = (HashValue, &dyn EntitySet)> + '_>;
+
+    /// Calls `f` with each hash in the index and its set, in no particular order. The same as
+    /// [`iter_hashes`](Self::iter_hashes), without the allocation.
+    fn for_each_hash(&self, f: &mut dyn FnMut(HashValue, &dyn EntitySet));
+
+    /// Writes the index to `writer` in `format`, using the codec registered for its value type with
+    /// [`register_index_codec`](index_codec::register_index_codec). Read it back with
+    /// [`deserialize_index`](index_codec::deserialize_index), which rebuilds the same kind of index with the same
+    /// hasher seed, or fails up front with [`CodecError::Unsupported`] if it couldn't.
+    #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+    fn serialize_into(&self, writer: &mut dyn Write, format: IndexFormat)
+    -> Result<(), IndexError>;
+
+    /// The number of values and entities in the index, the sizes of its sets and the memory it uses. See
+    /// [`index_stats`](super::index_stats).
+    fn stats(&self) -> IndexStats;
+
+    /// The index as `Any`, so it can be downcast back to its concrete type, e.g. `Index<T>`. Implement it by
+    /// returning `self`.
+    fn as_any(&self) -> &dyn Any;
+
+    /// The index as `Any`, so it can be downcast back to its concrete type, e.g. `Index<T>`.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// The boxed index as `Any`, so it can be downcast back to its concrete type, e.g. `Index<T>`.
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+
+    /// The name of the index's value type, as given by [`std::any::type_name`]. For messages only: the name isn't
+    /// guaranteed to be unique or stable.
+    fn value_type_name(&self) -> &'static str;
+}
+
+/// Checked downcasts back to the typed API. These only recognize an `Index<T>` with the default hasher and entity
+/// set; use [`as_any`](TypeErasedIndex::as_any) for anything else.
+impl dyn TypeErasedIndex {
+    /// The index as an `Index<T>`, if that's what it is.
+    pub fn downcast_ref<T: Hash + Eq + Clone + Any>(&self) -> Option<&Index<T>> {
+        self.as_any().downcast_ref()
+    }
+
+    /// The index as an `Index<T>`, if that's what it is.
+    pub fn downcast_mut<T: Hash + Eq + Clone + Any>(&mut self) -> Option<&mut Index<T>> {
+        self.as_any_mut().downcast_mut()
+    }
+
+    /// Like [`downcast_ref`](Self::downcast_ref), but reports an index that isn't an `Index<T>` as
+    /// [`IndexError::TypeMismatch`], with the index's [`value_type_name`](TypeErasedIndex::value_type_name) as what
+    /// was found. An index over `T` with another hasher or set type doesn't downcast either, and the two names are
+    /// then the same.
+    pub fn try_downcast_ref<T: Hash + Eq + Clone + Any>(&self) -> Result<&Index<T>, IndexError> {
+        let found = self.value_type_name();
+        self.downcast_ref().ok_or(IndexError::TypeMismatch {
+            expected: type_name::<T>(),
+            found,
+        })
+    }
+
+    /// Like [`downcast_mut`](Self::downcast_mut), but reports an index that isn't an `Index<T>` as
+    /// [`IndexError::TypeMismatch`]. See [`try_downcast_ref`](Self::try_downcast_ref).
+    pub fn try_downcast_mut<T: Hash + Eq + Clone + Any>(
+        &mut self,
+    ) -> Result<&mut Index<T>, IndexError> {
+        let found = self.value_type_name();
+        self.downcast_mut().ok_or(IndexError::TypeMismatch {
+            expected: type_name::<T>(),
+            found,
+        })
+    }
+
+    /// Unboxes the index as an `Index<T>`, or gives it back unchanged if it's something else.
+    pub fn downcast<T: Hash + Eq + Clone + Any>(self: Box<Self>) -> Result<Box<Index<T>>, BxIndex> {
+        if !self.as_any().is::<Index<T>>() {
+            return Err(self);
+        }
+        Ok(self
+            .into_any()
+            .downcast()
+            .expect("the index was just checked to be an `Index<T>`"))
+    }
 }
 
 /// A blanket implementation of the type-erased API for all `Index<T>`s.
-impl<T: Hash + Eq + Clone + Any> TypeErasedIndex for Index<T> {
+impl<T: Hash + Eq + Clone + Any, S: BuildHasher128 + 'static, E: EntitySet> TypeErasedIndex
+    for Index<T, S, E>
+{
     fn insert_entity_with_hash(
         &mut self,
         hash: HashValue,
         entity_id: EntityId,
-    ) -> Result<bool, ()> {
-        // Equality is determined by comparing the full 128-bit hashes. We do not expect any collisions before the heat
-        // death of the universe.
-        let hash128_equality = |(stored_value, _): &_| one_shot_128(stored_value) == hash;
+    ) -> Result<bool, IndexError> {
+        Self::check_entity_id(entity_id)?;
+        self.settle();
+        self.audit_hash(hash)?;
 
-        let entities = self
+        let (_, set) = self
             .lookup
-            .find_mut(hash as u64, hash128_equality)
-            .map(|(_, set)| set)
-            .ok_or(())?;
-        Ok(entities.insert(entity_id))
+            .find_mut(hash.as_u64(), hash128_equality(hash))
+            .ok_or(IndexError::UnknownHash(hash))?;
+        let before = SetSize::of(set);
+        let inserted = set.insert(entity_id);
+        self.sizes.resize(before, SetSize::of(set));
+        Ok(inserted)
     }
 
-    fn get_with_hash(&self, hash: HashValue) -> Option<&HashSet<EntityId>> {
-        // Equality is determined by comparing the full 128-bit hashes. We do not expect any collisions before the heat
-        // death of the universe.
-        let hash128_equality = |(stored_value, _): &_| one_shot_128(stored_value) == hash;
-        self.lookup
-            .find(hash as u64, hash128_equality)
-            .map(|(_, set)| set)
+    fn get_with_hash(&self, hash: HashValue) -> Option<&dyn EntitySet> {
+        self.set_with_hash(hash).map(|set| set as &dyn EntitySet)
     }
 
-    fn get_with_hash_mut(&mut self, hash: HashValue) -> Option<&mut HashSet<EntityId>> {
-        // Equality is determined by comparing the full 128-bit hashes. We do not expect any collisions before the heat
-        // death of the universe.
-        let hash128_equality = |(stored_value, _): &_| one_shot_128(stored_value) == hash;
-        self.lookup
-            .find_mut(hash as u64, hash128_equality)
-            .map(|(_, set)| set)
+    fn get_with_hash_mut(&mut self, hash: HashValue) -> Option<&mut dyn EntitySet> {
+        self.set_with_hash_mut(hash)
+            .map(|set| set as &mut dyn EntitySet)
     }
 
     fn has_hash(&self, hash: HashValue) -> bool {
-        self.get_with_hash(hash).is_some()
+        self.set_with_hash(hash).is_some()
+    }
+
+    fn remove_entity_with_hash(&mut self, hash: HashValue, entity_id: EntityId) -> bool {
+        self.settle();
+
+        let Ok(mut entry) = self
+            .lookup
+            .find_entry(hash.as_u64(), hash128_equality(hash))
+        else {
+            return false;
+        };
+        let (_, set) = entry.get_mut();
+        let before = SetSize::of(set);
+        let removed = set.remove(entity_id);
+        if set.is_empty() {
+            entry.remove();
+            self.sizes.remove(before);
+        } else {
+            self.sizes.resize(before, SetSize::of(set));
+        }
+        removed
+    }
+
+    fn move_entity_between_hashes(
+        &mut self,
+        from: HashValue,
+        to: HashValue,
+        entity_id: EntityId,
+    ) -> Result<bool, IndexError> {
+        if !self.has_hash(to) {
+            return Err(IndexError::UnknownHash(to));
+        }
+        if from == to {
+            return self
+                .insert_entity_with_hash(to, entity_id)
+                .map(|inserted| !inserted);
+        }
+        self.insert_entity_with_hash(to, entity_id)?;
+        Ok(self.remove_entity_with_hash(from, entity_id))
+    }
+
+    fn iter_hashes(&self) -> Box<dyn Iterator<Item = (HashValue, &dyn EntitySet)> + '_> {
+        Box::new(
+            self.lookup
+                .iter()
+                .map(|(value, set)| (value.fingerprint(), set as &dyn EntitySet)),
+        )
+    }
+
+    fn for_each_hash(&self, f: &mut dyn FnMut(HashValue, &dyn EntitySet)) {
+        for (value, set) in &self.lookup {
+            f(value.fingerprint(), set);
+        }
+    }
+
+    /// Fails with [`CodecError::Unsupported`] unless the index has the default set type and an [`Xxh3BuildHasher`],
+    /// whose seed the checkpoint records.
+    #[cfg(any(feature = "serde-json", feature = "serde-postcard"))]
+    fn serialize_into(
+        &self,
+        writer: &mut dyn Write,
+        format: IndexFormat,
+    ) -> Result<(), IndexError> {
+        self.serialize_as(IndexKind::Index, writer, format)
+    }
+
+    /// Reads the counters the index keeps, catching up with a set lent out by `&mut` if there is one.
+    fn stats(&self) -> IndexStats {
+        let mut sizes = Cow::Borrowed(&self.sizes);
+        if let Some((hash, before)) = self.lent
+            && let Some((_, set)) = self.lookup.find(hash.as_u64(), hash128_equality(hash))
+        {
+            sizes.to_mut().resize(before, SetSize::of(set));
+        }
+        sizes.stats(self.lookup.allocation_size(), self.lookup.num_buckets())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_index_agrees_with_one_shot_128() {
+        let mut index = Index::<String>::new();
+        index.insert_entity(&"a".to_string(), 1).unwrap();
+        assert_eq!(
+            index.hash_value(&"a".to_string()),
+            Fingerprint::of(&"a".to_string())
+        );
+        assert!(index.has_hash(Fingerprint::of(&"a".to_string())));
+    }
+
+    #[test]
+    fn seeded_index_uses_its_own_hashes() {
+        let mut index = Index::<String, _>::with_hasher(Xxh3BuildHasher::new(99));
+        let key = "a".to_string();
+        index.insert_entity(&key, 1).unwrap();
+        index.insert_entity(&key, 2).unwrap();
+
+        let hash = index.hash_value(&key);
+        assert_ne!(hash, Fingerprint::of(&key));
+        assert!(!index.has_hash(Fingerprint::of(&key)));
+        assert_eq!(index.get(&key).map(FastHashSet::len), Some(2));
+        assert!(index.insert_entity_with_hash(hash, 3).unwrap());
+        assert_eq!(index.get_with_hash(hash).map(|set| set.len()), Some(3));
+    }
+
+    #[test]
+    fn insert_value_replaces_an_existing_set() {
+        let mut index = Index::<u32>::new();
+        index.insert_entity(&7, 1).unwrap();
+        index
+            .insert_value(7, FastHashSet::from_iter([2, 3]))
+            .unwrap();
+        assert_eq!(index.get(&7), Some(&FastHashSet::from_iter([2, 3])));
+    }
+
+    #[test]
+    fn insert_value_lends_the_stored_set() {
+        let mut index = Index::<u32>::new();
+        index.insert_entity(&7, 1).unwrap();
+        let set = index
+            .insert_value(7, FastHashSet::from_iter([2, 3, 4]))
+            .unwrap();
+        set.remove(&3);
+        set.remove(&4);
+        index
+            .insert_value(8, FastHashSet::from_iter([5, 6]))
+            .unwrap()
+            .remove(&6);
+        assert_eq!(index.get(&7), Some(&FastHashSet::from_iter([2])));
+        let stats = index.stats();
+        assert_eq!((stats.distinct_values, stats.total_entities), (2, 2));
+    }
+
+    #[test]
+    fn removing_the_last_entity_removes_the_value() {
+        let mut index = Index::<&str>::new();
+        index.insert_entity(&"red", 1).unwrap();
+        index.insert_entity(&"red", 2).unwrap();
+
+        assert!(index.remove_entity(&"red", 1));
+        assert!(!index.remove_entity(&"red", 1));
+        assert_eq!(index.get(&"red"), Some(&FastHashSet::from_iter([2])));
+        assert!(index.remove_entity(&"red", 2));
+        assert!(!index.has_hash(index.hash_value(&"red")));
+        assert!(!index.remove_entity(&"blue", 2));
+
+        index.insert_entity(&"red", 3).unwrap();
+        assert_eq!(
+            index.remove_value(&"red"),
+            Some(FastHashSet::from_iter([3]))
+        );
+        assert_eq!(index.remove_value(&"red"), None);
+    }
+
+    #[test]
+    fn moving_an_entity_creates_and_prunes_sets() {
+        let mut index = Index::<&str>::new();
+        index.insert_entity(&"red", 1).unwrap();
+        index.insert_entity(&"red", 2).unwrap();
+
+        // The typed API creates the destination set.
+        assert!(index.move_entity(&"red", &"blue", 1).unwrap());
+        assert_eq!(index.get(&"red"), Some(&FastHashSet::from_iter([2])));
+        assert_eq!(index.get(&"blue"), Some(&FastHashSet::from_iter([1])));
+        assert!(index.move_entity(&"red", &"red", 2).unwrap());
+        assert!(!index.move_entity(&"red", &"red", 5).unwrap());
+        assert!(index.remove_entity(&"red", 5));
+
+        // The type-erased API can only move to an existing set, and leaves everything as it was if it can't.
+        let (red, blue, green) = (
+            index.hash_value(&"red"),
+            index.hash_value(&"blue"),
+            index.hash_value(&"green"),
+        );
+        assert!(matches!(
+            index.move_entity_between_hashes(red, green, 2),
+            Err(IndexError::UnknownHash(hash)) if hash == green
+        ));
+        assert_eq!(index.get(&"red"), Some(&FastHashSet::from_iter([2])));
+        assert!(index.move_entity_between_hashes(red, blue, 2).unwrap());
+        assert!(!index.has_hash(red));
+        assert_eq!(index.get(&"blue"), Some(&FastHashSet::from_iter([1, 2])));
+        assert!(!index.move_entity_between_hashes(red, blue, 3).unwrap());
+        assert!(index.move_entity_between_hashes(blue, blue, 3).unwrap());
+        assert!(!index.move_entity_between_hashes(blue, blue, 4).unwrap());
+    }
+
+    #[test]
+    fn try_getters_report_the_missing_hash() {
+        let mut index = Index::<&str>::new();
+        index.insert_entity(&"red", 1).unwrap();
+        let (red, blue) = (index.hash_value(&"red"), index.hash_value(&"blue"));
+
+        assert_eq!(index.try_get(&"red").unwrap(), &FastHashSet::from_iter([1]));
+        index.try_get_mut(&"red").unwrap().insert(2);
+        assert_eq!(index.try_get_with_hash(red).unwrap().len(), 2);
+        assert!(
+            matches!(index.try_get(&"blue"), Err(IndexError::UnknownHash(hash)) if hash == blue)
+        );
+
+        let error = index.try_get_with_hash_mut(blue).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("no value in the index has the hash {blue}")
+        );
+        assert!(matches!(
+            index.insert_entity_with_hash(blue, 3),
+            Err(IndexError::UnknownHash(hash)) if hash == blue
+        ));
+    }
+
+    #[test]
+    fn typed_iteration_yields_each_value_once() {
+        let mut index = Index::<&str>::new();
+        assert!(index.is_empty());
+        for (key, entity_id) in [("red", 1), ("blue", 2), ("red", 3)] {
+            index.insert_entity(&key, entity_id).unwrap();
+        }
+        assert_eq!(index.len(), 2);
+
+        let mut values: Vec<_> = index.values().copied().collect();
+        values.sort();
+        assert_eq!(values, ["blue", "red"]);
+        let mut sizes: Vec<_> = index
+            .iter()
+            .map(|(value, set)| (*value, set.len()))
+            .collect();
+        sizes.sort();
+        assert_eq!(sizes, [("blue", 1), ("red", 2)]);
+    }
+
+    #[test]
+    fn erased_iteration_walks_any_index() {
+        let mut colors = Index::<&str>::new();
+        colors.insert_entity(&"red", 1).unwrap();
+        colors.insert_entity(&"red", 2).unwrap();
+        let mut ages = Index::<u8>::new();
+        for (age, entity_id) in [(30, 1), (31, 2), (32, 3)] {
+            ages.insert_entity(&age, entity_id).unwrap();
+        }
+        let red = colors.hash_value(&"red");
+        let indexes: Vec<BxIndex> = vec![Box::new(colors), Box::new(ages)];
+
+        // Generic reporting code that knows nothing about the value types.
+        let entity_counts: Vec<usize> = indexes
+            .iter()
+            .map(|index| index.iter_hashes().map(|(_, set)| set.len()).sum())
+            .collect();
+        assert_eq!(entity_counts, [2, 3]);
+
+        let mut hashes = Vec::new();
+        indexes[0].for_each_hash(&mut |hash, set| hashes.push((hash, set.len())));
+        assert_eq!(hashes, [(red, 2)]);
+    }
+
+    #[test]
+    fn any_entity_set_can_back_an_index() {
+        use crate::type_erasure::entity_set::{DenseBitSet, RoaringSet, SortedVecSet};
+
+        fn fill<E: EntitySet>(mut index: Index<&'static str, Xxh3BuildHasher, E>) -> BxIndex {
+            for (key, entity_id) in [("red", 3), ("blue", 2), ("red", 1)] {
+                index.insert_entity(&key, entity_id).unwrap();
+            }
+            assert!(index.move_entity(&"blue", &"red", 2).unwrap());
+            Box::new(index)
+        }
+
+        let indexes = [
+            fill(Index::<_, _, FastHashSet<EntityId>>::default()),
+            fill(Index::<_, _, SortedVecSet>::default()),
+            fill(Index::<_, _, DenseBitSet>::default()),
+            fill(Index::<_, _, RoaringSet>::default()),
+        ];
+        let red = Fingerprint::of(&"red");
+        for index in &indexes {
+            let set = index.get_with_hash(red).unwrap();
+            assert_eq!(set.to_hash_set(), FastHashSet::from_iter([1, 2, 3]));
+            assert!(!index.has_hash(Fingerprint::of(&"blue")));
+        }
+
+        let mut sorted = Index::<&str, Xxh3BuildHasher, SortedVecSet>::default();
+        sorted.insert_entity(&"red", 9).unwrap();
+        sorted.insert_entity(&"red", 4).unwrap();
+        assert_eq!(sorted.get(&"red").unwrap().as_slice(), [4, 9]);
+    }
+
+    #[test]
+    fn ids_the_sets_cant_hold_are_errors() {
+        use crate::type_erasure::entity_set::DenseBitSet;
+
+        let mut index = Index::<&str, Xxh3BuildHasher, DenseBitSet>::default();
+        let error = index.insert_entity(&"red", EntityId::MAX).unwrap_err();
+        assert!(matches!(
+            error,
+            IndexError::EntityOutOfRange {
+                entity_id: EntityId::MAX,
+                max: DenseBitSet::MAX_ENTITY_ID
+            }
+        ));
+        // Nothing was created for the refused ID.
+        assert!(index.is_empty());
+
+        index.insert_entity(&"red", 1).unwrap();
+        let red = index.hash_value(&"red");
+        let erased: &mut dyn TypeErasedIndex = &mut index;
+        assert!(matches!(
+            erased.insert_entity_with_hash(red, EntityId::MAX),
+            Err(IndexError::EntityOutOfRange { .. })
+        ));
+        assert_eq!(erased.stats().total_entities, 1);
+    }
+
+    #[test]
+    fn boxed_indexes_downcast_to_their_own_type_only() {
+        let mut index = Index::<String>::new();
+        index.insert_entity(&"a".to_string(), 1).unwrap();
+        let mut boxed: BxIndex = Box::new(index);
+        assert_eq!(boxed.value_type_name(), type_name::<String>());
+
+        assert!(boxed.downcast_ref::<u32>().is_none());
+        boxed
+            .downcast_mut::<String>()
+            .unwrap()
+            .insert_value("b".to_string(), FastHashSet::from_iter([2]))
+            .unwrap();
+        assert_eq!(boxed.iter_hashes().count(), 2);
+
+        // A failed downcast hands the index back.
+        let Err(boxed) = boxed.downcast::<u32>() else {
+            panic!("an `Index<String>` was downcast to an `Index<u32>`");
+        };
+        assert_eq!(boxed.value_type_name(), type_name::<String>());
+        let index = boxed.downcast::<String>().ok().unwrap();
+        assert_eq!(
+            index.get(&"b".to_string()),
+            Some(&FastHashSet::from_iter([2]))
+        );
+    }
+
+    #[test]
+    fn failed_downcasts_name_both_types() {
+        let mut boxed: BxIndex = Box::new(Index::<String>::new());
+        let Err(error) = boxed.try_downcast_ref::<u32>() else {
+            panic!("an `Index<String>` was downcast to an `Index<u32>`");
+        };
+        assert!(matches!(
+            error,
+            IndexError::TypeMismatch { expected: "u32", found } if found == type_name::<String>()
+        ));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "expected a value of type `u32`, found `{}`",
+                type_name::<String>()
+            )
+        );
+        assert!(boxed.try_downcast_mut::<String>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stats_follow_every_change() {
+        /// What `stats` should report, recounted from scratch.
+        fn recount(index: &Index<u32>) -> (usize, usize, usize, usize, usize) {
+            let mut sizes: Vec<usize> = index.iter().map(|(_, set)| set.len()).collect();
+            sizes.sort_unstable();
+            let median = sizes.len().checked_sub(1).map_or(0, |last| sizes[last / 2]);
+            let total = sizes.iter().sum();
+            let heap_bytes = index.lookup.allocation_size()
+                + index
+                    .iter()
+                    .map(|(_, set)| set.allocation_size())
+                    .sum::<usize>();
+            (
+                sizes.len(),
+                total,
+                sizes.last().copied().unwrap_or(0),
+                median,
+                heap_bytes,
+            )
+        }
+
+        fn counts(stats: IndexStats) -> (usize, usize, usize, usize, usize) {
+            (
+                stats.distinct_values,
+                stats.total_entities,
+                stats.largest_set,
+                stats.median_set,
+                stats.heap_bytes,
+            )
+        }
+
+        let mut index = Index::<u32>::new();
+        assert_eq!(counts(index.stats()), (0, 0, 0, 0, 0));
+        for entity_id in 0..20 {
+            index
+                .insert_entity(&(entity_id as u32 % 3), entity_id)
+                .unwrap();
+        }
+        // The sets' own allocations are counted, not just their entities.
+        let heap_bytes = recount(&index).4;
+        assert!(heap_bytes > index.lookup.allocation_size() + 20 * std::mem::size_of::<EntityId>());
+        assert_eq!(counts(index.stats()), (3, 20, 7, 7, heap_bytes));
+        assert_eq!(index.stats().buckets, index.lookup.num_buckets());
+
+        // Changes through a lent `&mut` set show up before the next change, and without one.
+        index.get_mut(&0).unwrap().clear();
+        assert_eq!(counts(index.stats()), recount(&index));
+        index.try_get_mut(&1).unwrap().extend([100, 101, 102]);
+        index
+            .get_with_hash_mut(index.hash_value(&2))
+            .unwrap()
+            .insert(103);
+        assert_eq!(counts(index.stats()), recount(&index));
+
+        let (one, two) = (index.hash_value(&1), index.hash_value(&2));
+        index.insert_entity_with_hash(one, 104).unwrap();
+        index.move_entity_between_hashes(one, two, 1).unwrap();
+        index.move_entity(&2, &5, 2).unwrap();
+        index.remove_entity(&0, 99);
+        index
+            .insert_value(6, FastHashSet::default())
+            .unwrap()
+            .extend([7, 8]);
+        index.insert_value(1, FastHashSet::from_iter([9])).unwrap();
+        index.remove_value(&2);
+        assert_eq!(counts(index.stats()), (3, 4, 2, 1, recount(&index).4));
+    }
+
+    #[test]
+    fn stored_keys_are_never_rehashed() {
+        use std::{
+            hash::Hasher,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+
+        static HASHES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        struct Counted(u32);
+
+        impl Hash for Counted {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                HASHES.fetch_add(1, Ordering::Relaxed);
+                self.0.hash(state);
+            }
+        }
+
+        // Enough keys to make the table grow several times.
+        let mut index = Index::<Counted>::new();
+        for i in 0..1000 {
+            index.insert_entity(&Counted(i), i.into()).unwrap();
+        }
+        assert_eq!(HASHES.load(Ordering::Relaxed), 1000);
+
+        let key = index.hashed(Counted(7));
+        index.insert_entity_hashed(&key, 1).unwrap();
+        index.insert_entity_hashed(&key, 2).unwrap();
+        assert_eq!(HASHES.load(Ordering::Relaxed), 1001);
+        assert_eq!(index.get_hashed(&key).map(FastHashSet::len), Some(3));
+        assert!(index.insert_entity_with_hash(key.fingerprint(), 3).unwrap());
+        assert_eq!(
+            index.get_with_hash(key.fingerprint()).map(|set| set.len()),
+            Some(4)
+        );
+        let rehashes = usize::from(AUDIT_STORED_HASHES);
+        assert_eq!(HASHES.load(Ordering::Relaxed), 1001 + rehashes);
+    }
+
+    #[cfg(any(debug_assertions, feature = "collision-audit"))]
+    mod collision_audit {
+        use std::{cell::Cell, hash::Hasher, rc::Rc};
+
+        use super::*;
+        use crate::hashing::TruncatingBuildHasher;
+
+        #[test]
+        fn colliding_values_are_reported() {
+            // Every value hashes to zero.
+            let mut index = Index::<String, _>::with_hasher(TruncatingBuildHasher::new(0));
+            assert!(index.insert_entity(&"a".to_string(), 1).unwrap());
+            assert!(index.insert_entity(&"a".to_string(), 2).unwrap());
+
+            let is_collision = |error: IndexError| {
+                matches!(
+                    error,
+                    IndexError::Collision { hash, value_type, .. }
+                        if hash == Fingerprint::ZERO && value_type == type_name::<String>()
+                ) && error.colliding_values::<String>()
+                    == Some((&"a".to_string(), &"b".to_string()))
+            };
+            assert!(is_collision(
+                index.insert_entity(&"b".to_string(), 3).unwrap_err()
+            ));
+            assert!(is_collision(
+                index
+                    .insert_value("b".to_string(), FastHashSet::default())
+                    .unwrap_err()
+            ));
+            // Nothing was merged.
+            assert_eq!(index.get(&"a".to_string()).map(FastHashSet::len), Some(2));
+        }
+
+        #[test]
+        fn hash_impl_that_ignores_a_field_is_caught() {
+            #[derive(Clone, Debug, PartialEq, Eq)]
+            struct Person {
+                name: &'static str,
+                age: u8,
+            }
+
+            // Forgets about `age`.
+            impl Hash for Person {
+                fn hash<H: Hasher>(&self, state: &mut H) {
+                    self.name.hash(state);
+                }
+            }
+
+            let mut index = Index::<Person>::new();
+            let alice = Person {
+                name: "Alice",
+                age: 30,
+            };
+            let older_alice = Person {
+                age: 31,
+                ..alice.clone()
+            };
+            index.insert_entity(&alice, 1).unwrap();
+            let error = index.insert_entity(&older_alice, 2).unwrap_err();
+            assert!(
+                matches!(error, IndexError::Collision { hash, .. } if hash == index.hash_value(&alice))
+            );
+            assert_eq!(
+                error.colliding_values::<Person>(),
+                Some((&alice, &older_alice))
+            );
+            assert_eq!(error.colliding_values::<String>(), None);
+            assert!(error.to_string().contains("hash collision"));
+        }
+
+        /// A key whose hash can be changed after it's in the index.
+        #[derive(Clone, Debug)]
+        struct Chameleon {
+            id: u32,
+            color: Rc<Cell<u32>>,
+        }
+
+        impl PartialEq for Chameleon {
+            fn eq(&self, other: &Self) -> bool {
+                self.id == other.id
+            }
+        }
+
+        impl Eq for Chameleon {}
+
+        impl Hash for Chameleon {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.color.get().hash(state);
+            }
+        }
+
+        #[test]
+        fn cached_hashes_keep_disguised_keys_apart() {
+            let mut index = Index::<Chameleon>::new();
+            let first = Chameleon {
+                id: 1,
+                color: Rc::new(Cell::new(0)),
+            };
+            let second = Chameleon {
+                id: 2,
+                color: Rc::new(Cell::new(0)),
+            };
+            index.insert_entity(&first, 1).unwrap();
+
+            // `first` is stored under the hash it had when it was inserted, so disguising it doesn't let `second`
+            // sneak in under the same hash.
+            first.color.set(1);
+            assert!(index.insert_entity(&second, 2).is_err());
+        }
+
+        #[cfg(feature = "collision-audit")]
+        #[test]
+        fn erased_inserts_detect_mutated_keys() {
+            let mut index = Index::<Chameleon>::new();
+            let key = Chameleon {
+                id: 1,
+                color: Rc::new(Cell::new(0)),
+            };
+            index.insert_entity(&key, 1).unwrap();
+            let hash = index.hash_value(&key);
+
+            key.color.set(1);
+            // Lookups don't re-hash, so they still find the set.
+            assert!(index.get_with_hash(hash).is_some());
+            let error = index.insert_entity_with_hash(hash, 2).unwrap_err();
+            assert!(
+                matches!(error, IndexError::HashChanged { hash: changed, .. } if changed == hash)
+            );
+            assert!(error.to_string().contains("changed"));
+        }
     }
 }